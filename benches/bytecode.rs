@@ -0,0 +1,89 @@
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput,
+};
+use opcode_scan::evm::{Bytecode, BytecodeView, CodeBitmap};
+
+/// Dispatcher-like code of roughly `size` bytes: selector compares, jumps and arithmetic.
+fn sample_code(size: usize) -> Vec<u8> {
+    let block = [
+        0x5b, // JUMPDEST
+        0x60, 0x80, 0x60, 0x40, 0x52, // PUSH1 0x80 PUSH1 0x40 MSTORE
+        0x80, 0x63, 0xa9, 0x05, 0x9c, 0xbb, 0x14, // DUP1 PUSH4 selector EQ
+        0x61, 0x01, 0x23, 0x57, // PUSH2 0x0123 JUMPI
+        0x7f, // PUSH32, with data containing a JUMPDEST byte
+    ];
+    let mut code = Vec::with_capacity(size + 64);
+    while code.len() < size {
+        code.extend_from_slice(&block);
+        code.extend((0..32).map(|i| if i == 7 { 0x5b } else { i as u8 }));
+        code.extend_from_slice(&[0x01, 0x90, 0x56]); // ADD SWAP1 JUMP
+    }
+    code
+}
+
+fn histogram(c: &mut Criterion) {
+    let mut group = c.benchmark_group("histogram");
+    for size in [1024, 24576] {
+        let code = sample_code(size);
+        group.throughput(Throughput::Bytes(code.len() as u64));
+        group.bench_with_input(BenchmarkId::new("Bytecode", size), &code, |b, code| {
+            b.iter_batched(
+                || code.clone(),
+                |code| {
+                    let mut counts = [0u64; 256];
+                    let code = Bytecode::from(code);
+                    for op in code.elements().filter(|op| op.is_code) {
+                        counts[op.value as usize] += 1;
+                    }
+                    black_box(counts)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("BytecodeView", size), &code, |b, code| {
+            b.iter(|| {
+                let mut counts = [0u64; 256];
+                for (_, opcode, _) in BytecodeView::new(code).instructions() {
+                    counts[opcode.as_u8() as usize] += 1;
+                }
+                black_box(counts)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn jumpdests(c: &mut Criterion) {
+    let mut group = c.benchmark_group("jumpdests");
+    for size in [1024, 24576] {
+        let code = sample_code(size);
+        group.throughput(Throughput::Bytes(code.len() as u64));
+        group.bench_with_input(BenchmarkId::new("Bytecode", size), &code, |b, code| {
+            b.iter_batched(
+                || code.clone(),
+                |code| {
+                    let code = Bytecode::from(code);
+                    let valid = code
+                        .elements()
+                        .filter(|op| op.is_code && op.value == 0x5b)
+                        .count();
+                    black_box(valid)
+                },
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("CodeBitmap", size), &code, |b, code| {
+            b.iter(|| {
+                let bitmap = CodeBitmap::new(code);
+                let valid = (0..code.len())
+                    .filter(|pc| code[*pc] == 0x5b && bitmap.is_code(*pc))
+                    .count();
+                black_box(valid)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, histogram, jumpdests);
+criterion_main!(benches);
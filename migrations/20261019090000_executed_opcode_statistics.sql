@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS trace_tasks
+(
+    block_number INTEGER PRIMARY KEY NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS executed_opcode_statistics
+(
+    block_number INTEGER NOT NULL,
+    opcode       INTEGER NOT NULL,
+    count        INTEGER NOT NULL,
+    gas          INTEGER NOT NULL,
+    UNIQUE (block_number, opcode)
+);
+
+CREATE INDEX IF NOT EXISTS idx_executed_opcode_statistics_block_number ON executed_opcode_statistics (block_number);
+CREATE INDEX IF NOT EXISTS idx_executed_opcode_statistics_opcode ON executed_opcode_statistics (opcode);
@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS contract_calls
+(
+    block_number INTEGER NOT NULL,
+    address      BLOB    NOT NULL,
+    count        INTEGER NOT NULL,
+    UNIQUE (block_number, address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_contract_calls_address ON contract_calls (address);
+
+CREATE TABLE IF NOT EXISTS contract_opcode_statistics
+(
+    address BLOB    NOT NULL,
+    opcode  INTEGER NOT NULL,
+    count   INTEGER NOT NULL,
+    UNIQUE (address, opcode)
+);
+
+-- opcode histograms of called contracts, each weighted by the number of txs calling it
+CREATE VIEW IF NOT EXISTS weighted_opcode_statistics AS
+SELECT contract_calls.block_number                              AS block_number,
+       contract_opcode_statistics.opcode                        AS opcode,
+       SUM(contract_calls.count * contract_opcode_statistics.count) AS count
+FROM contract_calls
+         JOIN contract_opcode_statistics ON contract_opcode_statistics.address = contract_calls.address
+GROUP BY contract_calls.block_number, contract_opcode_statistics.opcode;
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS blocks
+(
+    block_number INTEGER PRIMARY KEY NOT NULL,
+    timestamp    INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_blocks_timestamp ON blocks (timestamp);
@@ -0,0 +1,9 @@
+-- single row of the latest throughput measured by the running scanner
+CREATE TABLE IF NOT EXISTS scan_rates
+(
+    id                  INTEGER PRIMARY KEY NOT NULL CHECK (id = 0),
+    head_block          INTEGER,
+    blocks_per_second   REAL    NOT NULL,
+    tx_tasks_per_second REAL    NOT NULL,
+    updated_at          INTEGER NOT NULL
+);
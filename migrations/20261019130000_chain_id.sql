@@ -0,0 +1,139 @@
+-- rows carry the chain they were scanned on, everything recorded so far is mainnet. Tables are
+-- rebuilt with their rowids, which incremental exports resume from.
+DROP VIEW IF EXISTS weighted_opcode_statistics;
+
+ALTER TABLE block_tasks RENAME TO block_tasks_old;
+CREATE TABLE block_tasks
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+INSERT INTO block_tasks (rowid, chain_id, block_number)
+SELECT rowid, 1, block_number
+FROM block_tasks_old;
+DROP TABLE block_tasks_old;
+
+ALTER TABLE tx_tasks RENAME TO tx_tasks_old;
+CREATE TABLE tx_tasks
+(
+    chain_id INTEGER NOT NULL,
+    tx_hash  BLOB    NOT NULL,
+    PRIMARY KEY (chain_id, tx_hash)
+);
+INSERT INTO tx_tasks (rowid, chain_id, tx_hash)
+SELECT rowid, 1, tx_hash
+FROM tx_tasks_old;
+DROP TABLE tx_tasks_old;
+
+ALTER TABLE trace_tasks RENAME TO trace_tasks_old;
+CREATE TABLE trace_tasks
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+INSERT INTO trace_tasks (rowid, chain_id, block_number)
+SELECT rowid, 1, block_number
+FROM trace_tasks_old;
+DROP TABLE trace_tasks_old;
+
+ALTER TABLE opcode_statistics RENAME TO opcode_statistics_old;
+CREATE TABLE opcode_statistics
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    opcode       INTEGER NOT NULL,
+    count        INTEGER NOT NULL,
+    UNIQUE (chain_id, block_number, opcode)
+);
+INSERT INTO opcode_statistics (rowid, chain_id, block_number, opcode, count)
+SELECT rowid, 1, block_number, opcode, count
+FROM opcode_statistics_old;
+DROP TABLE opcode_statistics_old;
+CREATE INDEX idx_opcode_statistics_opcode ON opcode_statistics (chain_id, opcode);
+
+ALTER TABLE executed_opcode_statistics RENAME TO executed_opcode_statistics_old;
+CREATE TABLE executed_opcode_statistics
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    opcode       INTEGER NOT NULL,
+    count        INTEGER NOT NULL,
+    gas          INTEGER NOT NULL,
+    UNIQUE (chain_id, block_number, opcode)
+);
+INSERT INTO executed_opcode_statistics (rowid, chain_id, block_number, opcode, count, gas)
+SELECT rowid, 1, block_number, opcode, count, gas
+FROM executed_opcode_statistics_old;
+DROP TABLE executed_opcode_statistics_old;
+CREATE INDEX idx_executed_opcode_statistics_opcode ON executed_opcode_statistics (chain_id, opcode);
+
+ALTER TABLE contract_calls RENAME TO contract_calls_old;
+CREATE TABLE contract_calls
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    address      BLOB    NOT NULL,
+    count        INTEGER NOT NULL,
+    UNIQUE (chain_id, block_number, address)
+);
+INSERT INTO contract_calls (rowid, chain_id, block_number, address, count)
+SELECT rowid, 1, block_number, address, count
+FROM contract_calls_old;
+DROP TABLE contract_calls_old;
+CREATE INDEX idx_contract_calls_address ON contract_calls (chain_id, address);
+
+ALTER TABLE contract_opcode_statistics RENAME TO contract_opcode_statistics_old;
+CREATE TABLE contract_opcode_statistics
+(
+    chain_id INTEGER NOT NULL,
+    address  BLOB    NOT NULL,
+    opcode   INTEGER NOT NULL,
+    count    INTEGER NOT NULL,
+    UNIQUE (chain_id, address, opcode)
+);
+INSERT INTO contract_opcode_statistics (rowid, chain_id, address, opcode, count)
+SELECT rowid, 1, address, opcode, count
+FROM contract_opcode_statistics_old;
+DROP TABLE contract_opcode_statistics_old;
+
+ALTER TABLE blocks RENAME TO blocks_old;
+CREATE TABLE blocks
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    timestamp    INTEGER NOT NULL,
+    PRIMARY KEY (chain_id, block_number)
+);
+INSERT INTO blocks (rowid, chain_id, block_number, timestamp)
+SELECT rowid, 1, block_number, timestamp
+FROM blocks_old;
+DROP TABLE blocks_old;
+CREATE INDEX idx_blocks_timestamp ON blocks (chain_id, timestamp);
+
+-- one row per chain of the latest throughput measured by its scanner
+ALTER TABLE scan_rates RENAME TO scan_rates_old;
+CREATE TABLE scan_rates
+(
+    chain_id            INTEGER PRIMARY KEY NOT NULL,
+    head_block          INTEGER,
+    blocks_per_second   REAL    NOT NULL,
+    tx_tasks_per_second REAL    NOT NULL,
+    updated_at          INTEGER NOT NULL
+);
+INSERT INTO scan_rates (chain_id, head_block, blocks_per_second, tx_tasks_per_second, updated_at)
+SELECT 1, head_block, blocks_per_second, tx_tasks_per_second, updated_at
+FROM scan_rates_old;
+DROP TABLE scan_rates_old;
+
+CREATE VIEW weighted_opcode_statistics AS
+SELECT contract_calls.chain_id                                  AS chain_id,
+       contract_calls.block_number                              AS block_number,
+       contract_opcode_statistics.opcode                        AS opcode,
+       SUM(contract_calls.count * contract_opcode_statistics.count) AS count
+FROM contract_calls
+         JOIN contract_opcode_statistics
+              ON contract_opcode_statistics.chain_id = contract_calls.chain_id
+                  AND contract_opcode_statistics.address = contract_calls.address
+GROUP BY contract_calls.chain_id, contract_calls.block_number, contract_opcode_statistics.opcode;
@@ -0,0 +1,120 @@
+-- incremental exports pick up rows by a sequence number bumped on every insert and update,
+-- upserts keep the rowid of the row they update
+CREATE TABLE export_sequence
+(
+    value INTEGER NOT NULL
+);
+
+ALTER TABLE opcode_statistics ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE opcode_statistics SET seq = rowid;
+CREATE INDEX idx_opcode_statistics_seq ON opcode_statistics (seq);
+
+ALTER TABLE executed_opcode_statistics ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE executed_opcode_statistics SET seq = rowid;
+CREATE INDEX idx_executed_opcode_statistics_seq ON executed_opcode_statistics (seq);
+
+ALTER TABLE contract_calls ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE contract_calls SET seq = rowid;
+CREATE INDEX idx_contract_calls_seq ON contract_calls (seq);
+
+ALTER TABLE contract_opcode_statistics ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE contract_opcode_statistics SET seq = rowid;
+CREATE INDEX idx_contract_opcode_statistics_seq ON contract_opcode_statistics (seq);
+
+ALTER TABLE blocks ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE blocks SET seq = rowid;
+CREATE INDEX idx_blocks_seq ON blocks (seq);
+
+INSERT INTO export_sequence (value)
+SELECT COALESCE(MAX(seq), 0)
+FROM (
+    SELECT MAX(seq) AS seq FROM opcode_statistics
+    UNION ALL
+    SELECT MAX(seq) AS seq FROM executed_opcode_statistics
+    UNION ALL
+    SELECT MAX(seq) AS seq FROM contract_calls
+    UNION ALL
+    SELECT MAX(seq) AS seq FROM contract_opcode_statistics
+    UNION ALL
+    SELECT MAX(seq) AS seq FROM blocks
+);
+
+CREATE TRIGGER opcode_statistics_insert_seq
+    AFTER INSERT
+    ON opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER opcode_statistics_update_seq
+    AFTER UPDATE OF count
+    ON opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER executed_opcode_statistics_insert_seq
+    AFTER INSERT
+    ON executed_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE executed_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER executed_opcode_statistics_update_seq
+    AFTER UPDATE OF count, gas
+    ON executed_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE executed_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER contract_calls_insert_seq
+    AFTER INSERT
+    ON contract_calls
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE contract_calls SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER contract_calls_update_seq
+    AFTER UPDATE OF count
+    ON contract_calls
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE contract_calls SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER contract_opcode_statistics_insert_seq
+    AFTER INSERT
+    ON contract_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE contract_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER contract_opcode_statistics_update_seq
+    AFTER UPDATE OF count
+    ON contract_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE contract_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER blocks_insert_seq
+    AFTER INSERT
+    ON blocks
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE blocks SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER blocks_update_seq
+    AFTER UPDATE OF timestamp
+    ON blocks
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE blocks SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
@@ -0,0 +1,19 @@
+-- opcode histograms of EOF code, kept apart as its bytes decode to other opcodes than legacy code
+CREATE TABLE eof_opcode_statistics
+(
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER NOT NULL,
+    opcode       INTEGER NOT NULL,
+    count        INTEGER NOT NULL,
+    UNIQUE (chain_id, block_number, opcode)
+);
+CREATE INDEX idx_eof_opcode_statistics_opcode ON eof_opcode_statistics (chain_id, opcode);
+
+CREATE TABLE contract_eof_opcode_statistics
+(
+    chain_id INTEGER NOT NULL,
+    address  BLOB    NOT NULL,
+    opcode   INTEGER NOT NULL,
+    count    INTEGER NOT NULL,
+    UNIQUE (chain_id, address, opcode)
+);
@@ -0,0 +1,6 @@
+-- migration steps of each analyzer run on this database, see `Analyzer::migrations`
+CREATE TABLE analyzer_migrations
+(
+    analyzer TEXT PRIMARY KEY NOT NULL,
+    version  INTEGER NOT NULL
+);
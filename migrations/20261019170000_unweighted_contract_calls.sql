@@ -0,0 +1,12 @@
+-- calls the weighted statistics leave out, as the called contract has no legacy opcode histogram,
+-- e.g. it was deployed before the scanned range or is EOF code
+CREATE VIEW unweighted_contract_calls AS
+SELECT contract_calls.chain_id          AS chain_id,
+       contract_calls.block_number      AS block_number,
+       SUM(contract_calls.count)        AS count
+FROM contract_calls
+         LEFT JOIN (SELECT DISTINCT chain_id, address FROM contract_opcode_statistics) AS histograms
+                   ON histograms.chain_id = contract_calls.chain_id
+                       AND histograms.address = contract_calls.address
+WHERE histograms.address IS NULL
+GROUP BY contract_calls.chain_id, contract_calls.block_number;
@@ -0,0 +1,57 @@
+-- deleted rows are recorded by key, so incremental exports can drop them downstream. Key columns a
+-- table does not have stay NULL.
+CREATE TABLE export_deletions
+(
+    seq          INTEGER NOT NULL,
+    table_name   TEXT    NOT NULL,
+    chain_id     INTEGER NOT NULL,
+    block_number INTEGER,
+    address      BLOB,
+    opcode       INTEGER
+);
+CREATE INDEX idx_export_deletions_seq ON export_deletions (table_name, seq);
+
+CREATE TRIGGER opcode_statistics_delete_seq
+    AFTER DELETE
+    ON opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, block_number, opcode)
+    VALUES ((SELECT value FROM export_sequence), 'opcode_statistics', OLD.chain_id, OLD.block_number, OLD.opcode);
+END;
+
+CREATE TRIGGER executed_opcode_statistics_delete_seq
+    AFTER DELETE
+    ON executed_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, block_number, opcode)
+    VALUES ((SELECT value FROM export_sequence), 'executed_opcode_statistics', OLD.chain_id, OLD.block_number, OLD.opcode);
+END;
+
+CREATE TRIGGER contract_calls_delete_seq
+    AFTER DELETE
+    ON contract_calls
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, block_number, address)
+    VALUES ((SELECT value FROM export_sequence), 'contract_calls', OLD.chain_id, OLD.block_number, OLD.address);
+END;
+
+CREATE TRIGGER contract_opcode_statistics_delete_seq
+    AFTER DELETE
+    ON contract_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, address, opcode)
+    VALUES ((SELECT value FROM export_sequence), 'contract_opcode_statistics', OLD.chain_id, OLD.address, OLD.opcode);
+END;
+
+CREATE TRIGGER blocks_delete_seq
+    AFTER DELETE
+    ON blocks
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, block_number)
+    VALUES ((SELECT value FROM export_sequence), 'blocks', OLD.chain_id, OLD.block_number);
+END;
@@ -0,0 +1,66 @@
+-- EOF histograms are exported like their legacy counterparts. Rows of invalid_jumps are keyed by
+-- their pc besides the address.
+ALTER TABLE export_deletions ADD COLUMN pc INTEGER;
+
+ALTER TABLE eof_opcode_statistics ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE eof_opcode_statistics SET seq = rowid;
+CREATE INDEX idx_eof_opcode_statistics_seq ON eof_opcode_statistics (seq);
+
+ALTER TABLE contract_eof_opcode_statistics ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+UPDATE contract_eof_opcode_statistics SET seq = rowid;
+CREATE INDEX idx_contract_eof_opcode_statistics_seq ON contract_eof_opcode_statistics (seq);
+
+UPDATE export_sequence
+SET value = MAX(value,
+                (SELECT COALESCE(MAX(seq), 0) FROM eof_opcode_statistics),
+                (SELECT COALESCE(MAX(seq), 0) FROM contract_eof_opcode_statistics));
+
+CREATE TRIGGER eof_opcode_statistics_insert_seq
+    AFTER INSERT
+    ON eof_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE eof_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER eof_opcode_statistics_update_seq
+    AFTER UPDATE OF count
+    ON eof_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE eof_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER eof_opcode_statistics_delete_seq
+    AFTER DELETE
+    ON eof_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, block_number, opcode)
+    VALUES ((SELECT value FROM export_sequence), 'eof_opcode_statistics', OLD.chain_id, OLD.block_number, OLD.opcode);
+END;
+
+CREATE TRIGGER contract_eof_opcode_statistics_insert_seq
+    AFTER INSERT
+    ON contract_eof_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE contract_eof_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER contract_eof_opcode_statistics_update_seq
+    AFTER UPDATE OF count
+    ON contract_eof_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    UPDATE contract_eof_opcode_statistics SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER contract_eof_opcode_statistics_delete_seq
+    AFTER DELETE
+    ON contract_eof_opcode_statistics
+BEGIN
+    UPDATE export_sequence SET value = value + 1;
+    INSERT INTO export_deletions (seq, table_name, chain_id, address, opcode)
+    VALUES ((SELECT value FROM export_sequence), 'contract_eof_opcode_statistics', OLD.chain_id, OLD.address, OLD.opcode);
+END;
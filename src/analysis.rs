@@ -0,0 +1,208 @@
+//! Pluggable analyses of deployed code.
+//!
+//! Every [`Analyzer`] is an independent plugin with its own tables: it creates them through its
+//! migration, derives an [`AnalysisOutput`] from a contract's code, and persists that output.
+//! The tx workers and `reanalyze` run whatever the [`Registry`] holds, so adding an analysis
+//! means implementing the trait and registering it, nothing in `tasks` changes.
+
+use crate::chain::Chain;
+use crate::evm::BytecodeView;
+use async_trait::async_trait;
+use ethers::types::Address;
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::any::Any;
+
+mod eof;
+mod histogram;
+mod jumps;
+mod ngram;
+mod proxy;
+mod selector;
+mod stack;
+
+pub use eof::EofAnalyzer;
+pub use histogram::HistogramAnalyzer;
+pub use jumps::JumpAnalyzer;
+pub use ngram::NgramAnalyzer;
+pub use proxy::ProxyAnalyzer;
+pub use selector::SelectorAnalyzer;
+pub use stack::StackAnalyzer;
+
+/// The deployment a code belongs to.
+#[derive(Clone, Debug)]
+pub struct ContractContext {
+    pub chain: Chain,
+    pub address: Address,
+    /// Unknown when reanalyzing txs stored before block numbers were recorded.
+    pub block_number: Option<u64>,
+    /// Timestamp of the deployment block, unknown if the block was not recorded.
+    pub timestamp: Option<u64>,
+}
+
+/// Result of [`Analyzer::analyze`], only understood by the analyzer that produced it.
+pub struct AnalysisOutput(Box<dyn Any + Send>);
+
+impl AnalysisOutput {
+    pub fn new<T: Any + Send>(output: T) -> Self {
+        Self(Box::new(output))
+    }
+
+    /// Take the output back, panics if it was produced by another analyzer.
+    pub fn downcast<T: Any>(self) -> T {
+        *self
+            .0
+            .downcast()
+            .expect("analysis output of another analyzer")
+    }
+}
+
+#[async_trait]
+pub trait Analyzer: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// SQL creating and then upgrading the analyzer's tables, one step per schema version. Each
+    /// step runs once per database and in order before anything is persisted, so steps are only
+    /// ever appended.
+    fn migrations(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether `persist` writes rows per block, which need the block number of the deployment.
+    fn requires_block_number(&self) -> bool {
+        false
+    }
+
+    /// Analyze the code of one contract, CPU only so it can run on any thread.
+    fn analyze(&self, ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput;
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Delete everything persisted per contract for the chain, before its stored code is
+    /// reanalyzed.
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error>;
+
+    /// Delete the rows persisted per block for the chain, only called when the block numbers of
+    /// all stored deployments are known so they can be rebuilt.
+    async fn reset_blocks(&self, _pool: &SqlitePool, _chain_id: u64) -> Result<(), sqlx::Error> {
+        Ok(())
+    }
+}
+
+/// Built-in analyzers, enabled by the `analyzers` config.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyzerKind {
+    /// Opcode histograms per block and per contract.
+    Histogram,
+    /// Opcode n-gram frequencies.
+    Ngram,
+    /// Function selectors of the dispatcher.
+    Selector,
+    /// Proxy patterns, EIP-1167 clones, EIP-1967 and other `DELEGATECALL` forwarders.
+    Proxy,
+    /// EOF containers and whether they validate.
+    Eof,
+    /// Static jumps to offsets that are not a `JUMPDEST`.
+    Jumps,
+    /// Stack heights and `DUPn`/`SWAPn` depths.
+    Stack,
+}
+
+#[derive(Default)]
+pub struct Registry {
+    analyzers: Vec<Box<dyn Analyzer>>,
+}
+
+impl Registry {
+    pub fn from_kinds(kinds: &[AnalyzerKind]) -> Self {
+        let mut registry = Self::default();
+        for kind in kinds {
+            match kind {
+                AnalyzerKind::Histogram => registry.register(HistogramAnalyzer),
+                AnalyzerKind::Ngram => registry.register(NgramAnalyzer::default()),
+                AnalyzerKind::Selector => registry.register(SelectorAnalyzer),
+                AnalyzerKind::Proxy => registry.register(ProxyAnalyzer),
+                AnalyzerKind::Eof => registry.register(EofAnalyzer),
+                AnalyzerKind::Jumps => registry.register(JumpAnalyzer),
+                AnalyzerKind::Stack => registry.register(StackAnalyzer),
+            };
+        }
+        registry
+    }
+
+    pub fn register(&mut self, analyzer: impl Analyzer + 'static) -> &mut Self {
+        self.analyzers.push(Box::new(analyzer));
+        self
+    }
+
+    pub fn analyzers(&self) -> impl Iterator<Item = &dyn Analyzer> {
+        self.analyzers.iter().map(|a| a.as_ref())
+    }
+
+    /// Run the migration steps of each analyzer the database has not seen yet.
+    pub async fn migrate(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        for analyzer in self.analyzers() {
+            let (version,): (i64,) = sqlx::query_as(
+                "SELECT COALESCE(MAX(version), 0) FROM analyzer_migrations WHERE analyzer = ?",
+            )
+            .bind(analyzer.name())
+            .fetch_one(pool)
+            .await?;
+            let steps = analyzer.migrations().iter().enumerate();
+            for (step, migration) in steps.skip(version as usize) {
+                let mut tx = pool.begin().await?;
+                sqlx::query(migration).execute(&mut *tx).await?;
+                sqlx::query(
+                    "INSERT OR REPLACE INTO analyzer_migrations (analyzer, version) VALUES (?, ?)",
+                )
+                .bind(analyzer.name())
+                .bind(step as i64 + 1)
+                .execute(&mut *tx)
+                .await?;
+                tx.commit().await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn analyze(&self, ctx: &ContractContext, code: &BytecodeView) -> Vec<AnalysisOutput> {
+        self.analyzers()
+            .map(|analyzer| analyzer.analyze(ctx, code))
+            .collect()
+    }
+
+    /// Persist the outputs of [`Registry::analyze`].
+    pub async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        outputs: Vec<AnalysisOutput>,
+    ) -> Result<(), sqlx::Error> {
+        for (analyzer, output) in self.analyzers().zip(outputs) {
+            analyzer.persist(pool, ctx, output).await?;
+        }
+        Ok(())
+    }
+
+    /// Reset the analyzers' rows of the chain, the per-block ones only if `blocks` is set.
+    pub async fn reset(
+        &self,
+        pool: &SqlitePool,
+        chain_id: u64,
+        blocks: bool,
+    ) -> Result<(), sqlx::Error> {
+        for analyzer in self.analyzers() {
+            analyzer.reset(pool, chain_id).await?;
+            if blocks {
+                analyzer.reset_blocks(pool, chain_id).await?;
+            }
+        }
+        Ok(())
+    }
+}
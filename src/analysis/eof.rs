@@ -0,0 +1,154 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::evm::BytecodeView;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+/// EOF containers with their shape and validation result, into `eof_contracts`.
+///
+/// Containers whose header does not parse are recorded with version 0 and the error.
+pub struct EofAnalyzer;
+
+struct EofSummary {
+    version: u8,
+    code_sections: usize,
+    container_sections: usize,
+    data_size: usize,
+    /// Why the container is invalid, `None` if it validates.
+    error: Option<String>,
+}
+
+#[async_trait]
+impl Analyzer for EofAnalyzer {
+    fn name(&self) -> &'static str {
+        "eof"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &[
+            r#"
+            CREATE TABLE IF NOT EXISTS eof_contracts
+            (
+                address            BLOB PRIMARY KEY NOT NULL,
+                version            INTEGER NOT NULL,
+                code_sections      INTEGER NOT NULL,
+                container_sections INTEGER NOT NULL,
+                data_size          INTEGER NOT NULL,
+                error              TEXT
+            );
+            "#,
+            // rows recorded before chains were told apart are mainnet
+            r#"
+            ALTER TABLE eof_contracts RENAME TO eof_contracts_old;
+            CREATE TABLE eof_contracts
+            (
+                chain_id           INTEGER NOT NULL,
+                address            BLOB    NOT NULL,
+                version            INTEGER NOT NULL,
+                code_sections      INTEGER NOT NULL,
+                container_sections INTEGER NOT NULL,
+                data_size          INTEGER NOT NULL,
+                error              TEXT,
+                PRIMARY KEY (chain_id, address)
+            );
+            INSERT INTO eof_contracts (chain_id, address, version, code_sections, container_sections, data_size, error)
+            SELECT 1, address, version, code_sections, container_sections, data_size, error
+            FROM eof_contracts_old;
+            DROP TABLE eof_contracts_old;
+            "#,
+            // sequence numbers and deletions for incremental exports
+            r#"
+            ALTER TABLE eof_contracts ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+            UPDATE eof_contracts SET seq = rowid;
+            CREATE INDEX idx_eof_contracts_seq ON eof_contracts (seq);
+            UPDATE export_sequence SET value = MAX(value, (SELECT COALESCE(MAX(seq), 0) FROM eof_contracts));
+
+            CREATE TRIGGER eof_contracts_insert_seq
+                AFTER INSERT
+                ON eof_contracts
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                UPDATE eof_contracts SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+            END;
+
+            CREATE TRIGGER eof_contracts_update_seq
+                AFTER UPDATE OF version, code_sections, container_sections, data_size, error
+                ON eof_contracts
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                UPDATE eof_contracts SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+            END;
+
+            CREATE TRIGGER eof_contracts_delete_seq
+                AFTER DELETE
+                ON eof_contracts
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                INSERT INTO export_deletions (seq, table_name, chain_id, address)
+                VALUES ((SELECT value FROM export_sequence), 'eof_contracts', OLD.chain_id, OLD.address);
+            END;
+            "#,
+        ]
+    }
+
+    fn analyze(&self, _ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let summary = code.eof().map(|eof| match eof {
+            Ok(eof) => EofSummary {
+                version: eof.version,
+                code_sections: eof.code_sections.len(),
+                container_sections: eof.container_sections.len(),
+                data_size: eof.data.len(),
+                error: eof.validate().err().map(|e| e.to_string()),
+            },
+            Err(e) => EofSummary {
+                version: 0,
+                code_sections: 0,
+                container_sections: 0,
+                data_size: 0,
+                error: Some(e.to_string()),
+            },
+        });
+        AnalysisOutput::new(summary)
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let chain_id = ctx.chain.id() as i64;
+        let address = ctx.address.as_bytes();
+        match output.downcast::<Option<EofSummary>>() {
+            Some(summary) => {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO eof_contracts (chain_id, address, version, code_sections, container_sections, data_size, error) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(chain_id)
+                .bind(address)
+                .bind(summary.version)
+                .bind(summary.code_sections as i64)
+                .bind(summary.container_sections as i64)
+                .bind(summary.data_size as i64)
+                .bind(summary.error)
+                .execute(pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM eof_contracts WHERE chain_id = ? AND address = ?")
+                    .bind(chain_id)
+                    .bind(address)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM eof_contracts WHERE chain_id = ?")
+            .bind(chain_id as i64)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
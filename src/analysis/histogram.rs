@@ -0,0 +1,113 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::db::{
+    append_eof_opcode_statistics, append_opcode_statistics, clear_opcode_statistics,
+    set_contract_opcode_statistics,
+};
+use crate::evm::{BytecodeView, OpcodeId};
+use crate::metrics::METRICS;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+/// Opcode histograms, into `opcode_statistics` and `contract_opcode_statistics`, or their `eof_`
+/// counterparts for EOF code.
+pub struct HistogramAnalyzer;
+
+struct Histogram {
+    /// Occurrences of each opcode present in the code.
+    counts: Vec<(u8, u64)>,
+    /// Whether the code has undefined opcodes, or ones not enabled on the chain at deployment.
+    has_invalid_opcodes: bool,
+    /// Whether the counts are of EOF opcodes.
+    eof: bool,
+}
+
+#[async_trait]
+impl Analyzer for HistogramAnalyzer {
+    fn name(&self) -> &'static str {
+        "histogram"
+    }
+
+    fn requires_block_number(&self) -> bool {
+        true
+    }
+
+    fn analyze(&self, ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let profile = ctx.chain.profile();
+        let mut counts = [0u64; 256];
+        let mut has_invalid_opcodes = false;
+        let mut count = |opcode: OpcodeId| {
+            counts[opcode.as_u8() as usize] += 1;
+            let enabled = ctx
+                .block_number
+                .and_then(|n| profile.is_enabled(opcode, n, ctx.timestamp));
+            has_invalid_opcodes |= match enabled {
+                Some(enabled) => !enabled,
+                None => opcode.is_other_invalid(),
+            };
+        };
+        let eof = match code.eof() {
+            // count the code sections, the header and data are not code
+            Some(Ok(eof)) => {
+                eof.all_instructions()
+                    .for_each(|(_, opcode, _)| count(opcode));
+                true
+            }
+            _ => {
+                code.instructions().for_each(|(_, opcode, _)| count(opcode));
+                false
+            }
+        };
+        let counts = counts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, count)| *count > 0)
+            .map(|(opcode, count)| (opcode as u8, count))
+            .collect::<Vec<_>>();
+        AnalysisOutput::new(Histogram {
+            counts,
+            has_invalid_opcodes,
+            eof,
+        })
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let histogram = output.downcast::<Histogram>();
+        if histogram.has_invalid_opcodes {
+            warn!("contract {:?} contains invalid opcodes", ctx.address);
+            METRICS.invalid_opcode_contracts.inc();
+        }
+        if let Some(block_number) = ctx.block_number {
+            for (opcode, count) in histogram.counts.iter() {
+                let chain_id = ctx.chain.id();
+                if histogram.eof {
+                    append_eof_opcode_statistics(pool, chain_id, block_number, *opcode, *count)
+                        .await?;
+                } else {
+                    append_opcode_statistics(pool, chain_id, block_number, *opcode, *count).await?;
+                }
+            }
+        }
+        set_contract_opcode_statistics(
+            pool,
+            ctx.chain.id(),
+            ctx.address,
+            &histogram.counts,
+            histogram.eof,
+        )
+        .await
+    }
+
+    async fn reset(&self, _pool: &SqlitePool, _chain_id: u64) -> Result<(), sqlx::Error> {
+        // contract histograms are replaced per contract
+        Ok(())
+    }
+
+    async fn reset_blocks(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        clear_opcode_statistics(pool, chain_id).await
+    }
+}
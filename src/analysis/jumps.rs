@@ -0,0 +1,138 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::evm::BytecodeView;
+use crate::metrics::METRICS;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+/// Static jumps to offsets that are not a `JUMPDEST`, into `invalid_jumps`.
+///
+/// Only `PUSHn target JUMP(I)` in reachable code is considered, so every recorded jump fails
+/// whenever it runs. Contracts with one are counted as `SELECT COUNT(DISTINCT address)`.
+pub struct JumpAnalyzer;
+
+#[async_trait]
+impl Analyzer for JumpAnalyzer {
+    fn name(&self) -> &'static str {
+        "jumps"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &[
+            r#"
+            CREATE TABLE IF NOT EXISTS invalid_jumps
+            (
+                address BLOB    NOT NULL,
+                pc      INTEGER NOT NULL,
+                target  BLOB    NOT NULL,
+                UNIQUE (address, pc)
+            );
+            "#,
+            // rows recorded before chains were told apart are mainnet
+            r#"
+            ALTER TABLE invalid_jumps RENAME TO invalid_jumps_old;
+            CREATE TABLE invalid_jumps
+            (
+                chain_id INTEGER NOT NULL,
+                address  BLOB    NOT NULL,
+                pc       INTEGER NOT NULL,
+                target   BLOB    NOT NULL,
+                UNIQUE (chain_id, address, pc)
+            );
+            INSERT INTO invalid_jumps (chain_id, address, pc, target)
+            SELECT 1, address, pc, target
+            FROM invalid_jumps_old;
+            DROP TABLE invalid_jumps_old;
+            "#,
+            // sequence numbers and deletions for incremental exports
+            r#"
+            ALTER TABLE invalid_jumps ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+            UPDATE invalid_jumps SET seq = rowid;
+            CREATE INDEX idx_invalid_jumps_seq ON invalid_jumps (seq);
+            UPDATE export_sequence SET value = MAX(value, (SELECT COALESCE(MAX(seq), 0) FROM invalid_jumps));
+
+            CREATE TRIGGER invalid_jumps_insert_seq
+                AFTER INSERT
+                ON invalid_jumps
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                UPDATE invalid_jumps SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+            END;
+
+            CREATE TRIGGER invalid_jumps_update_seq
+                AFTER UPDATE OF target
+                ON invalid_jumps
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                UPDATE invalid_jumps SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+            END;
+
+            CREATE TRIGGER invalid_jumps_delete_seq
+                AFTER DELETE
+                ON invalid_jumps
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                INSERT INTO export_deletions (seq, table_name, chain_id, address, pc)
+                VALUES ((SELECT value FROM export_sequence), 'invalid_jumps', OLD.chain_id, OLD.address, OLD.pc);
+            END;
+            "#,
+        ]
+    }
+
+    fn analyze(&self, _ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let jumps = code
+            .invalid_static_jumps()
+            .map(|jump| (jump.pc, jump.target.to_vec()))
+            .collect::<Vec<_>>();
+        AnalysisOutput::new(jumps)
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let jumps = output.downcast::<Vec<(usize, Vec<u8>)>>();
+        let chain_id = ctx.chain.id() as i64;
+        let address = ctx.address.as_bytes();
+        let mut tx = pool.begin().await?;
+        // only contracts not recorded yet are reported, so redeployments and reanalyzing the
+        // same code don't count them twice
+        let (recorded,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS(SELECT 1 FROM invalid_jumps WHERE chain_id = ? AND address = ?)",
+        )
+        .bind(chain_id)
+        .bind(address)
+        .fetch_one(&mut *tx)
+        .await?;
+        if !jumps.is_empty() && !recorded {
+            warn!("contract {:?} contains invalid jumps", ctx.address);
+            METRICS.invalid_jump_contracts.inc();
+        }
+        sqlx::query("DELETE FROM invalid_jumps WHERE chain_id = ? AND address = ?")
+            .bind(chain_id)
+            .bind(address)
+            .execute(&mut *tx)
+            .await?;
+        for (pc, target) in jumps {
+            sqlx::query(
+                "INSERT INTO invalid_jumps (chain_id, address, pc, target) VALUES (?, ?, ?, ?)",
+            )
+            .bind(chain_id)
+            .bind(address)
+            .bind(pc as i64)
+            .bind(target)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM invalid_jumps WHERE chain_id = ?")
+            .bind(chain_id as i64)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
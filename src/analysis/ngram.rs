@@ -0,0 +1,101 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::consts::NGRAM_SIZE;
+use crate::evm::BytecodeView;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+/// Frequencies of opcode sequences of length `n` over all contracts, into `opcode_ngrams`.
+///
+/// Push data is skipped, so `PUSH1 0x80 PUSH1 0x40` is the 2-gram `PUSH1 PUSH1`.
+pub struct NgramAnalyzer {
+    n: usize,
+}
+
+impl NgramAnalyzer {
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "n-grams need at least one opcode");
+        Self { n }
+    }
+}
+
+impl Default for NgramAnalyzer {
+    fn default() -> Self {
+        Self::new(NGRAM_SIZE)
+    }
+}
+
+#[async_trait]
+impl Analyzer for NgramAnalyzer {
+    fn name(&self) -> &'static str {
+        "ngram"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &[
+            r#"
+            CREATE TABLE IF NOT EXISTS opcode_ngrams
+            (
+                ngram BLOB PRIMARY KEY NOT NULL,
+                count INTEGER NOT NULL
+            );
+            "#,
+            // rows recorded before chains were told apart are mainnet
+            r#"
+            ALTER TABLE opcode_ngrams RENAME TO opcode_ngrams_old;
+            CREATE TABLE opcode_ngrams
+            (
+                chain_id INTEGER NOT NULL,
+                ngram    BLOB    NOT NULL,
+                count    INTEGER NOT NULL,
+                PRIMARY KEY (chain_id, ngram)
+            );
+            INSERT INTO opcode_ngrams (chain_id, ngram, count)
+            SELECT 1, ngram, count
+            FROM opcode_ngrams_old;
+            DROP TABLE opcode_ngrams_old;
+            "#,
+        ]
+    }
+
+    fn analyze(&self, _ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let opcodes = code
+            .instructions()
+            .map(|(_, opcode, _)| opcode.as_u8())
+            .collect::<Vec<_>>();
+        let mut counts = HashMap::<Vec<u8>, u64>::new();
+        for ngram in opcodes.windows(self.n) {
+            *counts.entry(ngram.to_vec()).or_default() += 1;
+        }
+        AnalysisOutput::new(counts)
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let counts = output.downcast::<HashMap<Vec<u8>, u64>>();
+        let mut tx = pool.begin().await?;
+        for (ngram, count) in counts {
+            sqlx::query(
+                "INSERT INTO opcode_ngrams (chain_id, ngram, count) VALUES (?, ?, ?) ON CONFLICT(chain_id, ngram) DO UPDATE SET count = count + excluded.count",
+            )
+            .bind(ctx.chain.id() as i64)
+            .bind(ngram)
+            .bind(count as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM opcode_ngrams WHERE chain_id = ?")
+            .bind(chain_id as i64)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::evm::{BytecodeView, OpcodeId};
+use async_trait::async_trait;
+use ethers::types::Address;
+use sqlx::SqlitePool;
+
+/// Runtime code of an EIP-1167 minimal proxy before and after the implementation address.
+const EIP1167_PREFIX: &[u8] = &[0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+const EIP1167_SUFFIX: &[u8] = &[
+    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd, 0x5b, 0xf3,
+];
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+const EIP1967_IMPLEMENTATION_SLOT: [u8; 32] = [
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28, 0x49, 0x2d, 0xb9, 0x8d,
+    0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9, 0x20, 0xa3, 0xca, 0x50, 0x5d, 0x38, 0x2b, 0xbc,
+];
+
+/// Proxy patterns, into `proxy_contracts`.
+pub struct ProxyAnalyzer;
+
+enum ProxyKind {
+    /// Minimal proxy with the implementation in the code.
+    Eip1167(Address),
+    /// Implementation read from the EIP-1967 slot.
+    Eip1967,
+    /// Any other code forwarding with `DELEGATECALL`.
+    DelegateCall,
+}
+
+impl ProxyKind {
+    fn name(&self) -> &'static str {
+        match self {
+            ProxyKind::Eip1167(_) => "eip1167",
+            ProxyKind::Eip1967 => "eip1967",
+            ProxyKind::DelegateCall => "delegatecall",
+        }
+    }
+}
+
+#[async_trait]
+impl Analyzer for ProxyAnalyzer {
+    fn name(&self) -> &'static str {
+        "proxy"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &[
+            r#"
+            CREATE TABLE IF NOT EXISTS proxy_contracts
+            (
+                address        BLOB PRIMARY KEY NOT NULL,
+                kind           TEXT NOT NULL,
+                implementation BLOB
+            );
+            "#,
+            // rows recorded before chains were told apart are mainnet
+            r#"
+            ALTER TABLE proxy_contracts RENAME TO proxy_contracts_old;
+            CREATE TABLE proxy_contracts
+            (
+                chain_id       INTEGER NOT NULL,
+                address        BLOB    NOT NULL,
+                kind           TEXT    NOT NULL,
+                implementation BLOB,
+                PRIMARY KEY (chain_id, address)
+            );
+            INSERT INTO proxy_contracts (chain_id, address, kind, implementation)
+            SELECT 1, address, kind, implementation
+            FROM proxy_contracts_old;
+            DROP TABLE proxy_contracts_old;
+            "#,
+        ]
+    }
+
+    fn analyze(&self, _ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let raw = code.bytes();
+        let kind = if raw.len() == EIP1167_PREFIX.len() + 20 + EIP1167_SUFFIX.len()
+            && raw.starts_with(EIP1167_PREFIX)
+            && raw.ends_with(EIP1167_SUFFIX)
+        {
+            let start = EIP1167_PREFIX.len();
+            Some(ProxyKind::Eip1167(Address::from_slice(
+                &raw[start..start + 20],
+            )))
+        } else if code.instructions().any(|(_, opcode, data)| {
+            opcode == OpcodeId::PUSH32 && data == EIP1967_IMPLEMENTATION_SLOT
+        }) {
+            Some(ProxyKind::Eip1967)
+        } else if code
+            .instructions()
+            .any(|(_, opcode, _)| opcode == OpcodeId::DELEGATECALL)
+        {
+            Some(ProxyKind::DelegateCall)
+        } else {
+            None
+        };
+        AnalysisOutput::new(kind)
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let chain_id = ctx.chain.id() as i64;
+        let address = ctx.address.as_bytes();
+        match output.downcast::<Option<ProxyKind>>() {
+            Some(kind) => {
+                let implementation = match &kind {
+                    ProxyKind::Eip1167(implementation) => Some(implementation.as_bytes().to_vec()),
+                    _ => None,
+                };
+                sqlx::query(
+                    "INSERT OR REPLACE INTO proxy_contracts (chain_id, address, kind, implementation) VALUES (?, ?, ?, ?)",
+                )
+                .bind(chain_id)
+                .bind(address)
+                .bind(kind.name())
+                .bind(implementation)
+                .execute(pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM proxy_contracts WHERE chain_id = ? AND address = ?")
+                    .bind(chain_id)
+                    .bind(address)
+                    .execute(pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM proxy_contracts WHERE chain_id = ?")
+            .bind(chain_id as i64)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
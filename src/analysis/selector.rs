@@ -0,0 +1,101 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::evm::{BytecodeView, OpcodeId};
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+/// Function selectors compared against in the dispatcher, into `contract_selectors`.
+///
+/// Matches `PUSH4 selector EQ` of solc and `PUSH4 selector DUP2 EQ` of the IR pipeline, which
+/// may also catch other 4 byte constants compared for equality.
+pub struct SelectorAnalyzer;
+
+#[async_trait]
+impl Analyzer for SelectorAnalyzer {
+    fn name(&self) -> &'static str {
+        "selector"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &[
+            r#"
+            CREATE TABLE IF NOT EXISTS contract_selectors
+            (
+                address  BLOB NOT NULL,
+                selector BLOB NOT NULL,
+                UNIQUE (address, selector)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_contract_selectors_selector ON contract_selectors (selector);
+            "#,
+            // rows recorded before chains were told apart are mainnet
+            r#"
+            ALTER TABLE contract_selectors RENAME TO contract_selectors_old;
+            CREATE TABLE contract_selectors
+            (
+                chain_id INTEGER NOT NULL,
+                address  BLOB    NOT NULL,
+                selector BLOB    NOT NULL,
+                UNIQUE (chain_id, address, selector)
+            );
+            INSERT INTO contract_selectors (chain_id, address, selector)
+            SELECT 1, address, selector
+            FROM contract_selectors_old;
+            DROP TABLE contract_selectors_old;
+            CREATE INDEX idx_contract_selectors_selector ON contract_selectors (selector);
+            "#,
+        ]
+    }
+
+    fn analyze(&self, _ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let instructions = code.instructions().collect::<Vec<_>>();
+        let is = |i: usize, op: OpcodeId| instructions.get(i).is_some_and(|(_, o, _)| *o == op);
+        let mut selectors = instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, opcode, data))| *opcode == OpcodeId::PUSH4 && data.len() == 4)
+            .filter(|(i, _)| {
+                is(i + 1, OpcodeId::EQ) || (is(i + 1, OpcodeId::DUP2) && is(i + 2, OpcodeId::EQ))
+            })
+            .map(|(_, (_, _, data))| data.to_vec())
+            .collect::<Vec<_>>();
+        selectors.sort();
+        selectors.dedup();
+        AnalysisOutput::new(selectors)
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let selectors = output.downcast::<Vec<Vec<u8>>>();
+        let chain_id = ctx.chain.id() as i64;
+        let address = ctx.address.as_bytes();
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM contract_selectors WHERE chain_id = ? AND address = ?")
+            .bind(chain_id)
+            .bind(address)
+            .execute(&mut *tx)
+            .await?;
+        for selector in selectors {
+            sqlx::query(
+                "INSERT INTO contract_selectors (chain_id, address, selector) VALUES (?, ?, ?)",
+            )
+            .bind(chain_id)
+            .bind(address)
+            .bind(selector)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM contract_selectors WHERE chain_id = ?")
+            .bind(chain_id as i64)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
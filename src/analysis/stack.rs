@@ -0,0 +1,174 @@
+use crate::analysis::{AnalysisOutput, Analyzer, ContractContext};
+use crate::evm::stack::{StackAnalysis, StackDepths};
+use crate::evm::BytecodeView;
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+
+/// Stack heights and the deepest `DUPn` and `SWAPn` used, into `contract_stack_depths`.
+///
+/// The distribution of depths over contracts is
+/// `SELECT max_dup, COUNT(*) FROM contract_stack_depths GROUP BY max_dup`. Heights of EOF
+/// containers are the declared ones, checked by validation, and `NULL` if it fails.
+pub struct StackAnalyzer;
+
+struct StackSummary {
+    depths: StackDepths,
+    max_height: Option<usize>,
+    underflow_blocks: usize,
+    overflow_blocks: usize,
+    unresolved_jumps: usize,
+    complete: bool,
+}
+
+#[async_trait]
+impl Analyzer for StackAnalyzer {
+    fn name(&self) -> &'static str {
+        "stack"
+    }
+
+    fn migrations(&self) -> &'static [&'static str] {
+        &[
+            r#"
+            CREATE TABLE IF NOT EXISTS contract_stack_depths
+            (
+                address          BLOB PRIMARY KEY NOT NULL,
+                max_dup          INTEGER NOT NULL,
+                max_swap         INTEGER NOT NULL,
+                max_height       INTEGER,
+                underflow_blocks INTEGER NOT NULL,
+                overflow_blocks  INTEGER NOT NULL,
+                unresolved_jumps INTEGER NOT NULL,
+                complete         BOOLEAN NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_contract_stack_depths_max_dup ON contract_stack_depths (max_dup);
+            CREATE INDEX IF NOT EXISTS idx_contract_stack_depths_max_swap ON contract_stack_depths (max_swap);
+            "#,
+            // rows recorded before chains were told apart are mainnet
+            r#"
+            ALTER TABLE contract_stack_depths RENAME TO contract_stack_depths_old;
+            CREATE TABLE contract_stack_depths
+            (
+                chain_id         INTEGER NOT NULL,
+                address          BLOB    NOT NULL,
+                max_dup          INTEGER NOT NULL,
+                max_swap         INTEGER NOT NULL,
+                max_height       INTEGER,
+                underflow_blocks INTEGER NOT NULL,
+                overflow_blocks  INTEGER NOT NULL,
+                unresolved_jumps INTEGER NOT NULL,
+                complete         BOOLEAN NOT NULL,
+                PRIMARY KEY (chain_id, address)
+            );
+            INSERT INTO contract_stack_depths (chain_id, address, max_dup, max_swap, max_height, underflow_blocks, overflow_blocks, unresolved_jumps, complete)
+            SELECT 1, address, max_dup, max_swap, max_height, underflow_blocks, overflow_blocks, unresolved_jumps, complete
+            FROM contract_stack_depths_old;
+            DROP TABLE contract_stack_depths_old;
+            CREATE INDEX idx_contract_stack_depths_max_dup ON contract_stack_depths (max_dup);
+            CREATE INDEX idx_contract_stack_depths_max_swap ON contract_stack_depths (max_swap);
+            "#,
+            // sequence numbers and deletions for incremental exports
+            r#"
+            ALTER TABLE contract_stack_depths ADD COLUMN seq INTEGER NOT NULL DEFAULT 0;
+            UPDATE contract_stack_depths SET seq = rowid;
+            CREATE INDEX idx_contract_stack_depths_seq ON contract_stack_depths (seq);
+            UPDATE export_sequence SET value = MAX(value, (SELECT COALESCE(MAX(seq), 0) FROM contract_stack_depths));
+
+            CREATE TRIGGER contract_stack_depths_insert_seq
+                AFTER INSERT
+                ON contract_stack_depths
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                UPDATE contract_stack_depths SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+            END;
+
+            CREATE TRIGGER contract_stack_depths_update_seq
+                AFTER UPDATE OF max_dup, max_swap, max_height, underflow_blocks, overflow_blocks, unresolved_jumps, complete
+                ON contract_stack_depths
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                UPDATE contract_stack_depths SET seq = (SELECT value FROM export_sequence) WHERE rowid = NEW.rowid;
+            END;
+
+            CREATE TRIGGER contract_stack_depths_delete_seq
+                AFTER DELETE
+                ON contract_stack_depths
+            BEGIN
+                UPDATE export_sequence SET value = value + 1;
+                INSERT INTO export_deletions (seq, table_name, chain_id, address)
+                VALUES ((SELECT value FROM export_sequence), 'contract_stack_depths', OLD.chain_id, OLD.address);
+            END;
+            "#,
+        ]
+    }
+
+    fn analyze(&self, _ctx: &ContractContext, code: &BytecodeView) -> AnalysisOutput {
+        let mut depths = StackDepths::default();
+        let summary = match code.eof() {
+            Some(Ok(eof)) => {
+                eof.all_instructions()
+                    .for_each(|(_, opcode, immediate)| depths.record(opcode, immediate));
+                StackSummary {
+                    depths,
+                    max_height: eof.validate().ok().map(|_| {
+                        eof.types
+                            .iter()
+                            .map(|ty| ty.max_stack_height as usize)
+                            .max()
+                            .unwrap_or_default()
+                    }),
+                    underflow_blocks: 0,
+                    overflow_blocks: 0,
+                    unresolved_jumps: 0,
+                    complete: true,
+                }
+            }
+            _ => {
+                code.instructions()
+                    .for_each(|(_, opcode, immediate)| depths.record(opcode, immediate));
+                let analysis = StackAnalysis::new(code);
+                StackSummary {
+                    depths,
+                    max_height: Some(analysis.max_height),
+                    underflow_blocks: analysis.underflows.len(),
+                    overflow_blocks: analysis.overflows.len(),
+                    unresolved_jumps: analysis.unresolved_jumps.len(),
+                    complete: analysis.complete,
+                }
+            }
+        };
+        AnalysisOutput::new(summary)
+    }
+
+    async fn persist(
+        &self,
+        pool: &SqlitePool,
+        ctx: &ContractContext,
+        output: AnalysisOutput,
+    ) -> Result<(), sqlx::Error> {
+        let summary = output.downcast::<StackSummary>();
+        sqlx::query(
+            "INSERT OR REPLACE INTO contract_stack_depths (chain_id, address, max_dup, max_swap, max_height, underflow_blocks, overflow_blocks, unresolved_jumps, complete) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(ctx.chain.id() as i64)
+        .bind(ctx.address.as_bytes())
+        .bind(summary.depths.max_dup as i64)
+        .bind(summary.depths.max_swap as i64)
+        .bind(summary.max_height.map(|height| height as i64))
+        .bind(summary.underflow_blocks as i64)
+        .bind(summary.overflow_blocks as i64)
+        .bind(summary.unresolved_jumps as i64)
+        .bind(summary.complete)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn reset(&self, pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM contract_stack_depths WHERE chain_id = ?")
+            .bind(chain_id as i64)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
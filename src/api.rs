@@ -0,0 +1,263 @@
+//! HTTP API serving the recorded statistics as JSON.
+
+use crate::db::{get_contract_opcode_statistics, get_progress, Progress};
+use crate::evm::OpcodeId;
+use crate::report::{bucketed_opcode_counts, build_report, BlockRange, Bucket, Report, Statistics};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        error!("database error: {}", e);
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+/// Chain of a query, the one served by default.
+#[derive(Deserialize)]
+struct ChainQuery {
+    chain_id: Option<u64>,
+}
+
+/// Half-open block range `[from, to)` of a query.
+#[derive(Deserialize)]
+struct RangeQuery {
+    chain_id: Option<u64>,
+    from: Option<u64>,
+    to: Option<u64>,
+    #[serde(default)]
+    statistics: Statistics,
+    top: Option<usize>,
+}
+
+impl RangeQuery {
+    fn range(&self) -> BlockRange {
+        BlockRange {
+            start: self.from.unwrap_or_default(),
+            end: self.to,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TimeseriesQuery {
+    #[serde(flatten)]
+    range: RangeQuery,
+    bucket: Option<Bucket>,
+}
+
+#[derive(Serialize)]
+struct OpcodeCount {
+    opcode: u8,
+    name: String,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct TimeseriesPoint {
+    bucket: String,
+    count: u64,
+    percentage: f64,
+}
+
+/// Routes answering for the chain `chain_id` unless a query asks for another.
+pub fn router(pool: SqlitePool, chain_id: u64) -> Router {
+    Router::new()
+        .route("/opcodes", get(opcodes))
+        .route("/opcodes/:name/timeseries", get(opcode_timeseries))
+        .route("/contracts/:address/opcodes", get(contract_opcodes))
+        .route("/progress", get(progress))
+        .with_state((pool, chain_id))
+}
+
+/// Serve `router` on `listen` until `running` is cleared.
+pub async fn serve(
+    router: Router,
+    listen: SocketAddr,
+    running: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("listening on {}", listen);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            while running.load(Ordering::SeqCst) {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+            }
+        })
+        .await?;
+    info!("gracefully shutdown");
+    Ok(())
+}
+
+async fn opcodes(
+    State((pool, chain_id)): State<(SqlitePool, u64)>,
+    Query(query): Query<RangeQuery>,
+) -> Result<Json<Report>, ApiError> {
+    let chain_id = query.chain_id.unwrap_or(chain_id);
+    let report = build_report(
+        &pool,
+        chain_id,
+        query.statistics,
+        query.range(),
+        None,
+        &[],
+        query.top,
+    )
+    .await?;
+    Ok(Json(report))
+}
+
+async fn opcode_timeseries(
+    State((pool, chain_id)): State<(SqlitePool, u64)>,
+    Path(name): Path<String>,
+    Query(query): Query<TimeseriesQuery>,
+) -> Result<Json<Vec<TimeseriesPoint>>, ApiError> {
+    let opcode = name
+        .to_uppercase()
+        .parse::<OpcodeId>()
+        .map_err(|e| ApiError(StatusCode::BAD_REQUEST, e))?;
+    let rows = bucketed_opcode_counts(
+        &pool,
+        query.range.chain_id.unwrap_or(chain_id),
+        query.range.statistics,
+        query.range.range(),
+        query.bucket.unwrap_or(Bucket::Day),
+        &[opcode.as_u8()],
+    )
+    .await?;
+    let points = rows
+        .into_iter()
+        .map(|row| TimeseriesPoint {
+            bucket: row.bucket,
+            count: row.count,
+            percentage: row.percentage,
+        })
+        .collect();
+    Ok(Json(points))
+}
+
+async fn contract_opcodes(
+    State((pool, chain_id)): State<(SqlitePool, u64)>,
+    Path(address): Path<Address>,
+    Query(query): Query<ChainQuery>,
+) -> Result<Json<Vec<OpcodeCount>>, ApiError> {
+    let chain_id = query.chain_id.unwrap_or(chain_id);
+    let counts = get_contract_opcode_statistics(&pool, chain_id, address).await?;
+    if counts.is_empty() {
+        return Err(ApiError(
+            StatusCode::NOT_FOUND,
+            format!("no statistics of contract {address:?}"),
+        ));
+    }
+    Ok(Json(
+        counts
+            .into_iter()
+            .map(|(opcode, count)| OpcodeCount {
+                opcode: opcode.as_u8(),
+                name: opcode.to_string(),
+                count,
+            })
+            .collect(),
+    ))
+}
+
+async fn progress(
+    State((pool, chain_id)): State<(SqlitePool, u64)>,
+    Query(query): Query<ChainQuery>,
+) -> Result<Json<Progress>, ApiError> {
+    let chain_id = query.chain_id.unwrap_or(chain_id);
+    Ok(Json(get_progress(&pool, chain_id).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{append_opcode_statistics, memory_sqlite, set_block_timestamp};
+    use axum::body::Body;
+    use axum::http::Request;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    const DAY: u64 = 86400;
+
+    /// Two days with a block each, PUSH0 3 and 1 times, ADD 1 and 5 times.
+    async fn router_with_statistics() -> Router {
+        let pool = memory_sqlite().await;
+        for (block_number, push0, add) in [(1, 3, 1), (2, 1, 5)] {
+            set_block_timestamp(&pool, 1, block_number, block_number * DAY)
+                .await
+                .unwrap();
+            append_opcode_statistics(&pool, 1, block_number, OpcodeId::PUSH0.as_u8(), push0)
+                .await
+                .unwrap();
+            append_opcode_statistics(&pool, 1, block_number, OpcodeId::ADD.as_u8(), add)
+                .await
+                .unwrap();
+        }
+        router(pool, 1)
+    }
+
+    async fn get(router: Router, uri: &str) -> (StatusCode, Value) {
+        let response = router
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn timeseries() {
+        let (status, points) =
+            get(router_with_statistics().await, "/opcodes/push0/timeseries").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            points,
+            serde_json::json!([
+                { "bucket": "1970-01-02", "count": 3, "percentage": 75.0 },
+                { "bucket": "1970-01-03", "count": 1, "percentage": 100.0 / 6.0 },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn timeseries_of_unknown_opcode() {
+        let (status, _) = get(router_with_statistics().await, "/opcodes/nope/timeseries").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn report() {
+        let (status, report) = get(router_with_statistics().await, "/opcodes?top=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["total"], 10);
+        assert_eq!(report["rows"].as_array().unwrap().len(), 1);
+        assert_eq!(report["rows"][0]["name"], "ADD");
+        assert_eq!(report["rows"][0]["count"], 6);
+
+        let (_, report) = get(router_with_statistics().await, "/opcodes?from=2").await;
+        assert_eq!(report["total"], 6);
+        assert_eq!(report["rows"][1]["name"], "PUSH0");
+        assert_eq!(report["rows"][1]["count"], 1);
+    }
+}
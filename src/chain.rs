@@ -0,0 +1,211 @@
+//! Profiles of the scanned chains.
+//!
+//! One database holds the statistics of several chains, every row carries the chain id of the
+//! profile the scanner ran with. The sled store is opened by one process at a time, so every
+//! chain has its own.
+
+use crate::consts::SLED_DB_PATH;
+use crate::evm::OpcodeId;
+use serde::Deserialize;
+
+/// Chain to scan, selected by the `chain` config.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Chain {
+    #[default]
+    Mainnet,
+    Sepolia,
+    /// Arbitrum One.
+    Arbitrum,
+    /// OP Mainnet.
+    Optimism,
+    Base,
+    /// Polygon PoS.
+    Polygon,
+    Scroll,
+}
+
+/// Hard forks introducing opcodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Fork {
+    Shanghai,
+    Cancun,
+}
+
+impl Fork {
+    /// Opcodes introduced by the fork.
+    pub fn opcodes(self) -> &'static [OpcodeId] {
+        match self {
+            Fork::Shanghai => &[OpcodeId::PUSH0],
+            Fork::Cancun => &[
+                OpcodeId::BLOBHASH,
+                OpcodeId::BLOBBASEFEE,
+                OpcodeId::TLOAD,
+                OpcodeId::TSTORE,
+                OpcodeId::MCOPY,
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Activation {
+    Block(u64),
+    /// Unix timestamp of the first block of the fork.
+    Timestamp(u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct ChainProfile {
+    /// Name of the chain in the config, also naming its sled store.
+    pub name: &'static str,
+    pub chain_id: u64,
+    /// First block scanned unless `start_block` is configured.
+    pub start_block: u64,
+    /// Activations of the forks on this chain or their equivalent on L2s, e.g. Canyon and
+    /// Ecotone of the OP Stack. Forks not listed are treated as active since genesis.
+    pub forks: &'static [(Fork, Activation)],
+    /// Opcodes of an active fork the chain does not support.
+    pub unsupported: &'static [OpcodeId],
+}
+
+const MAINNET: ChainProfile = ChainProfile {
+    name: "mainnet",
+    chain_id: 1,
+    // Shanghai, the first block with PUSH0
+    start_block: 17034870,
+    forks: &[
+        (Fork::Shanghai, Activation::Timestamp(1681338455)),
+        (Fork::Cancun, Activation::Timestamp(1710338135)),
+    ],
+    unsupported: &[],
+};
+
+const SEPOLIA: ChainProfile = ChainProfile {
+    name: "sepolia",
+    chain_id: 11155111,
+    start_block: 2990908,
+    forks: &[
+        (Fork::Shanghai, Activation::Timestamp(1677557088)),
+        (Fork::Cancun, Activation::Timestamp(1706655072)),
+    ],
+    unsupported: &[],
+};
+
+const ARBITRUM: ChainProfile = ChainProfile {
+    name: "arbitrum",
+    chain_id: 42161,
+    // Nitro genesis, classic blocks are only served by the classic node
+    start_block: 22207817,
+    forks: &[
+        // ArbOS 11
+        (Fork::Shanghai, Activation::Timestamp(1710424089)),
+        // ArbOS 20 Atlas
+        (Fork::Cancun, Activation::Timestamp(1710770400)),
+    ],
+    unsupported: &[],
+};
+
+const OP_STACK_FORKS: &[(Fork, Activation)] = &[
+    // Canyon
+    (Fork::Shanghai, Activation::Timestamp(1704992401)),
+    // Ecotone
+    (Fork::Cancun, Activation::Timestamp(1710374401)),
+];
+
+const OPTIMISM: ChainProfile = ChainProfile {
+    name: "optimism",
+    chain_id: 10,
+    // Bedrock, legacy blocks are only served by the legacy node
+    start_block: 105235063,
+    forks: OP_STACK_FORKS,
+    unsupported: &[],
+};
+
+const BASE: ChainProfile = ChainProfile {
+    name: "base",
+    chain_id: 8453,
+    start_block: 0,
+    forks: OP_STACK_FORKS,
+    unsupported: &[],
+};
+
+const POLYGON: ChainProfile = ChainProfile {
+    name: "polygon",
+    chain_id: 137,
+    start_block: 50523000,
+    forks: &[
+        (Fork::Shanghai, Activation::Block(50523000)),
+        // Napoli
+        (Fork::Cancun, Activation::Block(54876000)),
+    ],
+    // no blob txs
+    unsupported: &[OpcodeId::BLOBHASH],
+};
+
+const SCROLL: ChainProfile = ChainProfile {
+    name: "scroll",
+    chain_id: 534352,
+    start_block: 0,
+    forks: &[
+        (Fork::Shanghai, Activation::Block(0)),
+        // Curie
+        (Fork::Cancun, Activation::Block(7096836)),
+    ],
+    // no blob txs
+    unsupported: &[OpcodeId::BLOBHASH, OpcodeId::BLOBBASEFEE],
+};
+
+impl Chain {
+    pub fn profile(self) -> &'static ChainProfile {
+        match self {
+            Chain::Mainnet => &MAINNET,
+            Chain::Sepolia => &SEPOLIA,
+            Chain::Arbitrum => &ARBITRUM,
+            Chain::Optimism => &OPTIMISM,
+            Chain::Base => &BASE,
+            Chain::Polygon => &POLYGON,
+            Chain::Scroll => &SCROLL,
+        }
+    }
+
+    pub fn id(self) -> u64 {
+        self.profile().chain_id
+    }
+
+    /// Path of the sled store, mainnet keeps the one used before chains were distinguished.
+    pub fn sled_path(self) -> String {
+        match self {
+            Chain::Mainnet => SLED_DB_PATH.to_string(),
+            chain => format!("{}-{}", SLED_DB_PATH, chain.profile().name),
+        }
+    }
+}
+
+impl ChainProfile {
+    /// Whether `fork` is active in the block, assumed active if it activates by timestamp and
+    /// the block's is unknown.
+    pub fn is_active(&self, fork: Fork, block_number: u64, timestamp: Option<u64>) -> bool {
+        match self.forks.iter().find(|(f, _)| *f == fork) {
+            Some((_, Activation::Block(activation))) => block_number >= *activation,
+            Some((_, Activation::Timestamp(activation))) => {
+                timestamp.is_none_or(|timestamp| timestamp >= *activation)
+            }
+            None => true,
+        }
+    }
+
+    /// Whether an opcode introduced by a fork is enabled in the block, `None` for opcodes not
+    /// introduced by any of the forks.
+    pub fn is_enabled(
+        &self,
+        opcode: OpcodeId,
+        block_number: u64,
+        timestamp: Option<u64>,
+    ) -> Option<bool> {
+        let fork = [Fork::Shanghai, Fork::Cancun]
+            .into_iter()
+            .find(|fork| fork.opcodes().contains(&opcode))?;
+        Some(self.is_active(fork, block_number, timestamp) && !self.unsupported.contains(&opcode))
+    }
+}
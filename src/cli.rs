@@ -0,0 +1,55 @@
+use crate::chain::Chain;
+use crate::consts::CONFIG_PATH;
+use clap::{Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+pub mod asm;
+pub mod corpus;
+pub mod disasm;
+pub mod execute;
+pub mod export;
+pub mod reanalyze;
+pub mod report;
+pub mod status;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path of the config file.
+    #[arg(long, default_value = CONFIG_PATH)]
+    pub config: PathBuf,
+    /// Chain to work on, overriding `chain` of the config.
+    #[arg(long, value_enum, global = true)]
+    pub chain: Option<Chain>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scan the chain for contract deployments, the default.
+    Run,
+    /// Print the disassembly of code.
+    Disasm(disasm::Args),
+    /// Execute init code in an embedded EVM and show the executed opcodes.
+    Execute(execute::Args),
+    /// Aggregate the recorded opcode statistics over block or date ranges.
+    Report(report::Args),
+    /// Assemble mnemonic source into code.
+    Asm(asm::Args),
+    /// Export or import the stored codes as a deduplicated corpus.
+    Corpus(corpus::Args),
+    /// Export the recorded tables to Parquet or CSV files.
+    Export(export::Args),
+    /// Recompute derived tables from the stored code, without network access.
+    Reanalyze(reanalyze::Args),
+    /// Show progress and ETA of the scan, also while the scanner runs.
+    Status,
+    /// Serve the HTTP API without scanning.
+    Serve {
+        /// Address to listen on, defaults to `api_listen` of the config.
+        #[arg(long)]
+        listen: Option<SocketAddr>,
+    },
+}
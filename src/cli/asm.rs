@@ -0,0 +1,24 @@
+use crate::evm::assemble;
+use std::io::Read;
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// File with the assembly source, stdin if omitted.
+    file: Option<PathBuf>,
+}
+
+/// Assemble mnemonic source and print the code as hex.
+pub fn run(args: Args) -> anyhow::Result<()> {
+    let source = match args.file {
+        Some(file) => std::fs::read_to_string(file)?,
+        None => {
+            let mut source = String::new();
+            std::io::stdin().read_to_string(&mut source)?;
+            source
+        }
+    };
+    let code = assemble(&source)?;
+    println!("0x{}", hex::encode(code.to_bytes()));
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use crate::config::Config;
+use crate::corpus::{export_corpus, import_corpus, Encoding, Trees};
+use clap::Subcommand;
+use std::path::PathBuf;
+
+/// The sled db is locked by a running scanner, stop it first.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    #[command(subcommand)]
+    command: CorpusCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CorpusCommand {
+    /// Write the stored codes as a deduplicated corpus.
+    Export {
+        /// Output directory, or file with `--archive`.
+        #[arg(long, default_value = "corpus")]
+        out: PathBuf,
+        /// Write a single `.tar.gz` archive instead of a directory.
+        #[arg(long)]
+        archive: bool,
+        #[arg(long, value_enum, default_value_t)]
+        encoding: Encoding,
+    },
+    /// Read a corpus directory or archive into the sled db of the chain.
+    Import { input: PathBuf },
+}
+
+pub fn run(config: &Config, args: Args) -> anyhow::Result<()> {
+    let trees = Trees::open(&sled::open(config.chain.sled_path())?)?;
+    let summary = match args.command {
+        CorpusCommand::Export {
+            out,
+            archive,
+            encoding,
+        } => export_corpus(&trees, &out, archive, encoding)?,
+        CorpusCommand::Import { input } => import_corpus(&trees, &input)?,
+    };
+    println!(
+        "{} deployments, {} init codes, {} runtime codes",
+        summary.deployments, summary.init_codes, summary.runtime_codes
+    );
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use crate::config::Config;
+use crate::consts::CONTRACT_TREE;
+use crate::evm::disassemble;
+use clap::ArgGroup;
+use ethers::types::Address;
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+#[command(group(ArgGroup::new("input").required(true).args(["address", "hex", "file"])))]
+pub struct Args {
+    /// Address of a contract whose code is in the local `contract` tree.
+    #[arg(long)]
+    address: Option<Address>,
+    /// Code as a hex string.
+    #[arg(long)]
+    hex: Option<String>,
+    /// File containing raw code.
+    #[arg(long)]
+    file: Option<PathBuf>,
+}
+
+pub fn run(config: &Config, args: Args) -> anyhow::Result<()> {
+    let code = if let Some(address) = args.address {
+        let contract_db = sled::open(config.chain.sled_path())?.open_tree(CONTRACT_TREE)?;
+        contract_db
+            .get(address.as_bytes())?
+            .ok_or_else(|| anyhow::anyhow!("no code of contract {address:?}"))?
+            .to_vec()
+    } else if let Some(hex) = args.hex {
+        hex::decode(hex.trim().trim_start_matches("0x"))?
+    } else {
+        std::fs::read(args.file.unwrap())?
+    };
+    print!("{}", disassemble(&code));
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use crate::config::Config;
+use crate::consts::{INIT_CODE_TREE, TX_BLOCK_NUMBER_TREE};
+use crate::db::{get_block_timestamp, get_tx_block_number, init_sqlite};
+use crate::evm::OpcodeId;
+use crate::executor::{execute_init_code, spec_id};
+use clap::ArgGroup;
+use ethers::types::H256;
+use ethers::utils::keccak256;
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+#[command(group(ArgGroup::new("input").required(true).args(["tx", "hex", "file"])))]
+pub struct Args {
+    /// Hash of a deployment tx whose init code is in the local `init_code` tree.
+    #[arg(long)]
+    tx: Option<H256>,
+    /// Init code as a hex string.
+    #[arg(long)]
+    hex: Option<String>,
+    /// File containing raw init code.
+    #[arg(long)]
+    file: Option<PathBuf>,
+    /// Block whose EVM rules apply, the deployment's block for `--tx` and the latest rules of
+    /// the chain if unset.
+    #[arg(long)]
+    block: Option<u64>,
+}
+
+pub async fn run(config: &Config, args: Args) -> anyhow::Result<()> {
+    let mut block = args.block;
+    let init_code = if let Some(tx_hash) = args.tx {
+        let sled_db = sled::open(config.chain.sled_path())?;
+        if block.is_none() {
+            let tx_block_number = sled_db.open_tree(TX_BLOCK_NUMBER_TREE)?;
+            block = get_tx_block_number(&tx_block_number, tx_hash.as_bytes())?;
+        }
+        sled_db
+            .open_tree(INIT_CODE_TREE)?
+            .get(tx_hash.as_bytes())?
+            .ok_or_else(|| anyhow::anyhow!("no init code of tx {tx_hash:?}"))?
+            .to_vec()
+    } else if let Some(hex) = args.hex {
+        hex::decode(hex.trim().trim_start_matches("0x"))?
+    } else {
+        std::fs::read(args.file.unwrap())?
+    };
+
+    let spec = match block {
+        Some(block) => {
+            let pool = init_sqlite().await?;
+            let timestamp = get_block_timestamp(&pool, config.chain.id(), block).await?;
+            spec_id(config.chain.profile(), block, timestamp)
+        }
+        None => spec_id(config.chain.profile(), u64::MAX, None),
+    };
+    let execution = execute_init_code(&init_code, spec)?;
+    println!("spec: {:?}", spec);
+    println!("success: {}", execution.success);
+    println!("gas used: {}", execution.gas_used);
+    println!(
+        "runtime code: {} bytes, hash 0x{}",
+        execution.runtime_code.len(),
+        hex::encode(keccak256(&execution.runtime_code))
+    );
+    println!("executed opcodes:");
+    let mut counts = execution
+        .opcode_counts
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .collect::<Vec<_>>();
+    counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    for (opcode, count) in counts {
+        println!(
+            "  {:<16}{}",
+            OpcodeId::from(opcode as u8).to_string(),
+            count
+        );
+    }
+    Ok(())
+}
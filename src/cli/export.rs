@@ -0,0 +1,170 @@
+use crate::analysis::Registry;
+use crate::cli::report::RangeArg;
+use crate::config::Config;
+use crate::db::init_sqlite;
+use crate::export::{
+    bucketed_name, export_buckets, export_deletions, export_table, schema_doc, table_exists,
+    Format, Table, Watermarks,
+};
+use crate::provider::pool_provider;
+use crate::report::{BlockRange, Bucket, Statistics};
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Tables to export, all by default.
+    #[arg(long, value_enum)]
+    table: Vec<Table>,
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+    /// Directory the files, `schema.md` and the watermarks are written to, one per chain for
+    /// incremental exports.
+    #[arg(long, default_value = "export")]
+    out: PathBuf,
+    /// Blocks to export, in the format of `report --range`.
+    #[arg(long, conflicts_with = "incremental")]
+    range: Option<RangeArg>,
+    /// Only export rows added or changed since the last incremental export to the same
+    /// directory, a changed row supersedes the one with the same key in earlier increments.
+    /// Keys of rows deleted since are exported to `<table>_deletions-*`.
+    #[arg(long)]
+    incremental: bool,
+    /// Export the opcode counts of `--statistics` per time bucket instead of the tables.
+    #[arg(long, value_enum, conflicts_with_all = ["incremental", "table"])]
+    bucket: Option<Bucket>,
+    /// Statistics of the bucketed export.
+    #[arg(long, value_enum, default_value_t, requires = "bucket")]
+    statistics: Statistics,
+}
+
+pub async fn run(config: &Config, args: Args) -> anyhow::Result<()> {
+    let chain_id = config.chain.id();
+    let pool = init_sqlite().await?;
+    let range = match args.range {
+        Some(range) => {
+            let provider = range
+                .has_time()
+                .then(|| pool_provider(config))
+                .transpose()?;
+            range.resolve(&pool, chain_id, provider.as_ref()).await?
+        }
+        None => BlockRange {
+            start: 0,
+            end: None,
+        },
+    };
+    let tables = if args.table.is_empty() {
+        Table::ALL.to_vec()
+    } else {
+        args.table
+    };
+    let extension = match args.format {
+        Format::Parquet => "parquet",
+        Format::Csv => "csv",
+    };
+
+    std::fs::create_dir_all(&args.out)?;
+    std::fs::write(args.out.join("schema.md"), schema_doc(args.format))?;
+    if let Some(bucket) = args.bucket {
+        let name = bucketed_name(args.statistics, bucket);
+        let path = args.out.join(format!("{}.{}", name, extension));
+        let rows = export_buckets(
+            &pool,
+            chain_id,
+            args.statistics,
+            range,
+            bucket,
+            args.format,
+            &path,
+        )
+        .await?;
+        info!("exported {} rows of {}", rows, name);
+        return Ok(());
+    }
+    let mut exported = vec![];
+    for table in tables {
+        if let Some(kind) = table.analyzer() {
+            if !table_exists(&pool, table).await? {
+                info!("skipping {}, its analyzer never ran", table.name());
+                continue;
+            }
+            // tables of earlier analyzer versions lack the sequence numbers
+            Registry::from_kinds(&[kind]).migrate(&pool).await?;
+        }
+        exported.push(table);
+    }
+    let mut watermarks = match args.incremental {
+        true => Watermarks::load(&args.out)?,
+        false => Watermarks::default(),
+    };
+    // one read transaction, so rows and deletions of all tables are of the same snapshot
+    let mut snapshot = pool.begin().await?;
+    for table in exported {
+        let (path, after_seq) = if args.incremental {
+            // named after the previous watermark, which is unique per increment
+            let after_seq = watermarks.get(table);
+            let path = args.out.join(format!(
+                "{}-{}.{}",
+                table.name(),
+                after_seq.unwrap_or_default(),
+                extension
+            ));
+            (path, after_seq)
+        } else {
+            (
+                args.out.join(format!("{}.{}", table.name(), extension)),
+                None,
+            )
+        };
+        let summary = export_table(
+            &mut snapshot,
+            chain_id,
+            table,
+            args.format,
+            range,
+            after_seq,
+            &path,
+        )
+        .await?;
+        if args.incremental {
+            match summary.last_seq {
+                Some(seq) => watermarks.set(table, seq),
+                // nothing new, don't leave empty increments behind
+                None => std::fs::remove_file(&path)?,
+            }
+        }
+        info!("exported {} rows of {}", summary.rows, table.name());
+
+        // the first increment is a snapshot, which has nothing to delete
+        if let (Some(after_seq), false) = (after_seq, table.key().is_empty()) {
+            let path = args.out.join(format!(
+                "{}_deletions-{}.{}",
+                table.name(),
+                after_seq,
+                extension
+            ));
+            let deletions = export_deletions(
+                &mut snapshot,
+                chain_id,
+                table,
+                args.format,
+                after_seq,
+                &path,
+            )
+            .await?;
+            match deletions.last_seq {
+                Some(seq) => {
+                    let seq = seq.max(watermarks.get(table).unwrap_or_default());
+                    watermarks.set(table, seq);
+                }
+                None => std::fs::remove_file(&path)?,
+            }
+            info!("exported {} deletions of {}", deletions.rows, table.name());
+        }
+    }
+    snapshot.commit().await?;
+    if args.incremental {
+        watermarks.save(&args.out)?;
+    }
+    Ok(())
+}
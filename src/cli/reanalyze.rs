@@ -0,0 +1,95 @@
+use crate::analysis::{AnalyzerKind, ContractContext, Registry};
+use crate::config::Config;
+use crate::consts::{CONTRACT_TREE, TX_BLOCK_NUMBER_TREE, TX_CONTRACT_ADDRESS_TREE};
+use crate::db::{get_block_timestamps, get_tx_block_number, init_sqlite};
+use crate::evm::BytecodeView;
+use ethers::types::Address;
+use rayon::prelude::*;
+
+/// Deployments analyzed and written per round.
+const CHUNK_SIZE: usize = 10000;
+
+/// Recompute the rows of analyzers for the chain from the code in its sled db, without network
+/// access.
+///
+/// The sled db is locked by a running scanner, stop it first.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Analyzers to rerun, the configured ones by default.
+    #[arg(long, value_enum)]
+    analyzer: Vec<AnalyzerKind>,
+}
+
+pub async fn run(config: &Config, args: Args) -> anyhow::Result<()> {
+    let kinds = if args.analyzer.is_empty() {
+        &config.analyzers
+    } else {
+        &args.analyzer
+    };
+    let analyzers = Registry::from_kinds(kinds);
+
+    let chain = config.chain;
+    let pool = init_sqlite().await?;
+    let sled_db = sled::open(chain.sled_path())?;
+    let tx_contract_db = sled_db.open_tree(TX_CONTRACT_ADDRESS_TREE)?;
+    let contract_db = sled_db.open_tree(CONTRACT_TREE)?;
+    let tx_block_db = sled_db.open_tree(TX_BLOCK_NUMBER_TREE)?;
+
+    let timestamps = get_block_timestamps(&pool, chain.id()).await?;
+    let mut deployments = vec![];
+    for item in tx_contract_db.iter() {
+        let (tx_hash, address) = item?;
+        let block_number = get_tx_block_number(&tx_block_db, &tx_hash)?;
+        deployments.push(ContractContext {
+            chain,
+            address: Address::from_slice(&address),
+            block_number,
+            timestamp: block_number.and_then(|n| timestamps.get(&n).copied()),
+        });
+    }
+    info!("{} deployments stored", deployments.len());
+    let unknown = deployments
+        .iter()
+        .filter(|ctx| ctx.block_number.is_none())
+        .count();
+    // the per-block rows can't be rebuilt without every block number, they are kept as scanned
+    let rebuild_blocks = unknown == 0;
+    if !rebuild_blocks {
+        for analyzer in analyzers.analyzers().filter(|a| a.requires_block_number()) {
+            warn!(
+                "{unknown} deployments have no recorded block number, keeping the per-block rows \
+                of {}",
+                analyzer.name()
+            );
+        }
+    }
+    analyzers.migrate(&pool).await?;
+    analyzers.reset(&pool, chain.id(), rebuild_blocks).await?;
+
+    for (i, chunk) in deployments.chunks(CHUNK_SIZE).enumerate() {
+        let outputs = chunk
+            .par_iter()
+            .map(|ctx| -> sled::Result<_> {
+                let code = contract_db.get(ctx.address.as_bytes())?;
+                Ok(code
+                    .filter(|code| !code.is_empty())
+                    .map(|code| analyzers.analyze(ctx, &BytecodeView::new(&code))))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (ctx, outputs) in chunk.iter().zip(outputs) {
+            if let Some(outputs) = outputs {
+                let ctx = ContractContext {
+                    block_number: ctx.block_number.filter(|_| rebuild_blocks),
+                    ..ctx.clone()
+                };
+                analyzers.persist(&pool, &ctx, outputs).await?;
+            }
+        }
+        info!(
+            "reanalyzed {} of {} deployments",
+            (i * CHUNK_SIZE + chunk.len()),
+            deployments.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,306 @@
+use crate::config::Config;
+use crate::db::{get_first_block_at, init_sqlite};
+use crate::evm::OpcodeId;
+use crate::provider::{pool_provider, PoolProvider};
+use crate::report::{
+    bucketed_opcode_counts, build_report, BlockRange, Bucket, BucketRow, Report, Statistics,
+};
+use chrono::{DateTime, NaiveDate};
+use ethers::prelude::*;
+use sqlx::SqlitePool;
+use std::str::FromStr;
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Blocks to report, `START..END` of block numbers or dates (`YYYY-MM-DD` or RFC 3339).
+    /// END is exclusive, either side may be omitted.
+    #[arg(long, default_value = "..")]
+    range: RangeArg,
+    /// Range to compare against, in the same format as `--range`.
+    #[arg(long, conflicts_with = "bucket")]
+    compare: Option<RangeArg>,
+    /// Report per time bucket, using the recorded block timestamps.
+    #[arg(long, value_enum)]
+    bucket: Option<Bucket>,
+    /// Only show these opcodes, e.g. `--opcode SELFDESTRUCT`.
+    #[arg(long)]
+    opcode: Vec<OpcodeId>,
+    #[arg(long, value_enum, default_value_t)]
+    statistics: Statistics,
+    /// Only show the N most frequent opcodes.
+    #[arg(long)]
+    top: Option<usize>,
+    #[arg(long, value_enum, default_value_t)]
+    format: Format,
+}
+
+#[derive(Copy, Clone, Debug, Default, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Bound {
+    Block(u64),
+    /// Unix timestamp, resolved to the first block at or after it.
+    Time(u64),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RangeArg {
+    start: Option<Bound>,
+    end: Option<Bound>,
+}
+
+impl FromStr for Bound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(block_number) = s.parse::<u64>() {
+            return Ok(Bound::Block(block_number));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            let time = date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+            return Ok(Bound::Time(time.timestamp() as u64));
+        }
+        DateTime::parse_from_rfc3339(s)
+            .map(|time| Bound::Time(time.timestamp() as u64))
+            .map_err(|_| format!("{s} is neither a block number nor a date"))
+    }
+}
+
+impl FromStr for RangeArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| format!("{s} is not a range, expected START..END"))?;
+        let parse = |s: &str| match s.trim() {
+            "" => Ok(None),
+            s => s.parse().map(Some),
+        };
+        Ok(Self {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+impl RangeArg {
+    pub fn has_time(&self) -> bool {
+        matches!(self.start, Some(Bound::Time(_))) || matches!(self.end, Some(Bound::Time(_)))
+    }
+
+    pub async fn resolve(
+        &self,
+        pool: &SqlitePool,
+        chain_id: u64,
+        provider: Option<&PoolProvider>,
+    ) -> anyhow::Result<BlockRange> {
+        let resolve = |bound: Bound| async move {
+            match bound {
+                Bound::Block(block_number) => Ok(block_number),
+                Bound::Time(timestamp) => {
+                    first_block_at(pool, chain_id, provider.unwrap(), timestamp).await
+                }
+            }
+        };
+        let start = match self.start {
+            Some(bound) => resolve(bound).await?,
+            None => 0,
+        };
+        let end = match self.end {
+            Some(bound) => Some(resolve(bound).await?),
+            None => None,
+        };
+        Ok(BlockRange { start, end })
+    }
+}
+
+/// First block with a timestamp at or after `timestamp`.
+///
+/// Looked up in the recorded block timestamps, or binary searched on the node for times after
+/// the last recorded block.
+async fn first_block_at(
+    pool: &SqlitePool,
+    chain_id: u64,
+    provider: &PoolProvider,
+    timestamp: u64,
+) -> anyhow::Result<u64> {
+    if let Some(block_number) = get_first_block_at(pool, chain_id, timestamp).await? {
+        return Ok(block_number);
+    }
+    let (mut low, mut high) = (0, provider.get_block_number().await?.as_u64() + 1);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let block = provider
+            .get_block(mid)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("block #{mid} not found"))?;
+        if block.timestamp.as_u64() < timestamp {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    Ok(low)
+}
+
+pub async fn run(config: &Config, args: Args) -> anyhow::Result<()> {
+    let chain_id = config.chain.id();
+    let pool = init_sqlite().await?;
+    let needs_provider = args.range.has_time() || args.compare.is_some_and(|r| r.has_time());
+    let provider = needs_provider.then(|| pool_provider(config)).transpose()?;
+
+    let range = args
+        .range
+        .resolve(&pool, chain_id, provider.as_ref())
+        .await?;
+    let opcodes = args.opcode.iter().map(|op| op.as_u8()).collect::<Vec<_>>();
+    if let Some(bucket) = args.bucket {
+        let mut rows =
+            bucketed_opcode_counts(&pool, chain_id, args.statistics, range, bucket, &opcodes)
+                .await?;
+        if let Some(top) = args.top {
+            // rows are sorted by frequency within each bucket
+            let mut rank = 0;
+            let mut previous = None;
+            rows.retain(|row| {
+                if previous.as_ref() != Some(&row.bucket) {
+                    previous = Some(row.bucket.clone());
+                    rank = 0;
+                }
+                rank += 1;
+                rank <= top
+            });
+        }
+        match args.format {
+            Format::Table => print_bucket_table(&rows),
+            Format::Csv => print_bucket_csv(&rows),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        }
+        return Ok(());
+    }
+
+    let compare = match args.compare {
+        Some(compare) => Some(compare.resolve(&pool, chain_id, provider.as_ref()).await?),
+        None => None,
+    };
+    let report = build_report(
+        &pool,
+        chain_id,
+        args.statistics,
+        range,
+        compare,
+        &opcodes,
+        args.top,
+    )
+    .await?;
+    match args.format {
+        Format::Table => print_table(&report),
+        Format::Csv => print_csv(&report),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+fn format_range(range: &BlockRange) -> String {
+    match range.end {
+        Some(end) => format!("#{}..#{}", range.start, end),
+        None => format!("#{}..", range.start),
+    }
+}
+
+fn print_table(report: &Report) {
+    println!(
+        "{:?} opcodes in blocks {} of chain {}, total {}",
+        report.statistics,
+        format_range(&report.range),
+        report.chain_id,
+        report.total
+    );
+    if let (Some(range), Some(total)) = (&report.compare_range, report.compare_total) {
+        println!(
+            "compared with blocks {}, total {}",
+            format_range(range),
+            total
+        );
+    }
+    if let Some(calls) = report.unweighted_calls.filter(|calls| *calls > 0) {
+        println!(
+            "{} calls to contracts without an opcode histogram are not weighted",
+            calls
+        );
+    }
+    print!("{:<8}{:<16}{:>16}{:>10}", "OPCODE", "NAME", "COUNT", "%");
+    if report.compare_range.is_some() {
+        print!("{:>16}{:>10}{:>10}", "COMPARE", "%", "DELTA");
+    }
+    println!();
+    for row in report.rows.iter() {
+        print!(
+            "{:<8}{:<16}{:>16}{:>10.4}",
+            format!("0x{:02x}", row.opcode),
+            row.name,
+            row.count,
+            row.percentage
+        );
+        if let (Some(count), Some(percentage), Some(delta)) =
+            (row.compare_count, row.compare_percentage, row.delta)
+        {
+            print!("{:>16}{:>10.4}{:>+10.4}", count, percentage, delta);
+        }
+        println!();
+    }
+}
+
+fn print_csv(report: &Report) {
+    print!("opcode,name,count,percentage");
+    if report.compare_range.is_some() {
+        print!(",compare_count,compare_percentage,delta");
+    }
+    println!();
+    for row in report.rows.iter() {
+        print!(
+            "{},{},{},{}",
+            row.opcode, row.name, row.count, row.percentage
+        );
+        if let (Some(count), Some(percentage), Some(delta)) =
+            (row.compare_count, row.compare_percentage, row.delta)
+        {
+            print!(",{},{},{}", count, percentage, delta);
+        }
+        println!();
+    }
+}
+
+fn print_bucket_table(rows: &[BucketRow]) {
+    println!(
+        "{:<18}{:<8}{:<16}{:>16}{:>10}",
+        "BUCKET", "OPCODE", "NAME", "COUNT", "%"
+    );
+    for row in rows {
+        println!(
+            "{:<18}{:<8}{:<16}{:>16}{:>10.4}",
+            row.bucket,
+            format!("0x{:02x}", row.opcode),
+            row.name,
+            row.count,
+            row.percentage
+        );
+    }
+}
+
+fn print_bucket_csv(rows: &[BucketRow]) {
+    println!("bucket,opcode,name,count,percentage");
+    for row in rows {
+        println!(
+            "{},{},{},{},{}",
+            row.bucket, row.opcode, row.name, row.count, row.percentage
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::config::Config;
+use crate::db::{get_progress, get_scan_rates, init_sqlite};
+use crate::progress::{format_duration, format_eta, unix_now};
+use std::time::Duration;
+
+/// Print the progress of the scan of the chain, readable while the scanner runs.
+pub async fn run(config: &Config) -> anyhow::Result<()> {
+    let profile = config.chain.profile();
+    let pool = init_sqlite().await?;
+    let progress = get_progress(&pool, profile.chain_id).await?;
+    let rates = get_scan_rates(&pool, profile.chain_id).await?;
+
+    println!("{:<24}{} ({})", "chain", profile.name, profile.chain_id);
+
+    match progress.latest_processed_block {
+        Some(latest) => println!("{:<24}#{}", "latest processed block", latest),
+        None => println!("{:<24}none", "latest processed block"),
+    }
+    if let Some(head) = rates.as_ref().and_then(|r| r.head_block) {
+        let lag = head.saturating_sub(progress.latest_processed_block.unwrap_or_default());
+        println!("{:<24}#{} ({} behind)", "head block", head, lag);
+    }
+
+    let Some(rates) = rates else {
+        println!("{:<24}{}", "block tasks", progress.block_tasks);
+        println!("{:<24}{}", "tx tasks", progress.tx_tasks);
+        println!("{:<24}{}", "trace tasks", progress.trace_tasks);
+        println!("no rates recorded yet, ETA unknown");
+        return Ok(());
+    };
+    println!(
+        "{:<24}{}, {:.2}/s, ETA {}",
+        "block tasks",
+        progress.block_tasks,
+        rates.blocks_per_second,
+        format_eta(progress.block_tasks, rates.blocks_per_second)
+    );
+    println!(
+        "{:<24}{}, {:.2}/s, ETA {}",
+        "tx tasks",
+        progress.tx_tasks,
+        rates.tx_tasks_per_second,
+        format_eta(progress.tx_tasks, rates.tx_tasks_per_second)
+    );
+    println!("{:<24}{}", "trace tasks", progress.trace_tasks);
+    let age = Duration::from_secs(unix_now().saturating_sub(rates.updated_at));
+    println!("{:<24}{} ago", "rates updated", format_duration(age));
+    Ok(())
+}
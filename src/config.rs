@@ -0,0 +1,149 @@
+use crate::analysis::AnalyzerKind;
+use crate::chain::Chain;
+use crate::consts::{
+    BLOCK_BATCH_SIZE, HEALTH_CHECK_INTERVAL_SECS, HTTP_PROVIDER, POLL_INTERVAL_SECS,
+    PROGRESS_INTERVAL_SECS, TX_BATCH_SIZE, WS_PROVIDER,
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Scanner configuration, read from a TOML file.
+///
+/// Every field is optional in the file, missing ones fall back to the defaults in `consts`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Chain to scan, checked against the node's chain id.
+    pub chain: Chain,
+    /// First block scanned, the chain's default start block if unset. Only read before the
+    /// first block was recorded.
+    pub start_block: Option<u64>,
+    /// WebSocket endpoint, only used by the subscribe head follower.
+    pub ws_provider: String,
+    /// HTTP endpoint used by the block and tx workers when no `endpoints` are configured.
+    pub http_provider: String,
+    /// HTTP endpoints of the provider pool.
+    pub endpoints: Vec<EndpointConfig>,
+    /// Seconds between health checks of the pool endpoints.
+    pub health_check_interval: u64,
+    /// Block tasks whose blocks are fetched in one JSON-RPC batch.
+    pub block_batch_size: u32,
+    /// Tx tasks whose receipts and codes are fetched in one JSON-RPC batch.
+    pub tx_batch_size: u32,
+    /// Where deployed code is fetched from, in order of preference.
+    pub code_sources: Vec<CodeSource>,
+    /// Analyzers run on every deployed contract.
+    pub analyzers: Vec<AnalyzerKind>,
+    /// Tracer for executed opcode statistics, not collected if unset.
+    pub opcode_tracer: Option<OpcodeTracer>,
+    /// Address the HTTP API listens on while scanning, disabled if unset.
+    pub api_listen: Option<SocketAddr>,
+    /// Address the Prometheus metrics are exported on, disabled if unset.
+    pub metrics_listen: Option<SocketAddr>,
+    /// Seconds between progress logs.
+    pub progress_interval: u64,
+    /// How the listener learns about new blocks.
+    pub head_follower: HeadFollowerKind,
+    /// Seconds between `eth_blockNumber` polls of the poll head follower.
+    pub poll_interval: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EndpointConfig {
+    pub url: String,
+    /// Relative share of requests sent to this endpoint, must be at least 1.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Maximum requests per second, unlimited if unset.
+    pub rate_limit: Option<u32>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeadFollowerKind {
+    /// `eth_subscribe("newHeads")` over `ws_provider`.
+    #[default]
+    Subscribe,
+    /// `eth_blockNumber` polling over `http_provider`.
+    Poll,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeSource {
+    /// `eth_getCode` at the deployment block, needs archive state.
+    State,
+    /// Output of the creation frame of `debug_traceTransaction`, needs the debug namespace but
+    /// no archive state within the node's re-execution window.
+    Trace,
+    /// `eth_getCode` at the latest block, empty for self-destructed contracts.
+    Latest,
+    /// Local execution of the stored init code against an empty state, needs no node.
+    Execute,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpcodeTracer {
+    /// JS tracer aggregating on the node, cheap to transfer.
+    Js,
+    /// Default struct logger, for nodes without JS tracer support.
+    StructLogs,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            chain: Chain::default(),
+            start_block: None,
+            ws_provider: WS_PROVIDER.to_string(),
+            http_provider: HTTP_PROVIDER.to_string(),
+            endpoints: vec![],
+            health_check_interval: HEALTH_CHECK_INTERVAL_SECS,
+            block_batch_size: BLOCK_BATCH_SIZE,
+            tx_batch_size: TX_BATCH_SIZE,
+            code_sources: vec![CodeSource::State, CodeSource::Latest],
+            analyzers: vec![AnalyzerKind::Histogram],
+            opcode_tracer: None,
+            api_listen: None,
+            metrics_listen: None,
+            progress_interval: PROGRESS_INTERVAL_SECS,
+            head_follower: HeadFollowerKind::default(),
+            poll_interval: POLL_INTERVAL_SECS,
+        }
+    }
+}
+
+impl Config {
+    /// Load the config from `path`, or use the defaults if the file does not exist.
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            info!("{} not found, using default config", path.display());
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn start_block(&self) -> u64 {
+        self.start_block.unwrap_or(self.chain.profile().start_block)
+    }
+
+    /// Endpoints of the provider pool, falling back to `http_provider` alone.
+    pub fn endpoints(&self) -> Vec<EndpointConfig> {
+        if !self.endpoints.is_empty() {
+            return self.endpoints.clone();
+        }
+        vec![EndpointConfig {
+            url: self.http_provider.clone(),
+            weight: default_weight(),
+            rate_limit: None,
+        }]
+    }
+}
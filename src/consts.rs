@@ -1,7 +1,13 @@
 pub const WS_PROVIDER: &str = "ws://localhost:8545";
 pub const HTTP_PROVIDER: &str = "http://localhost:8545";
-pub const SHANGHAI_FORK: u64 = 17034870;
+pub const POLL_INTERVAL_SECS: u64 = 12;
+pub const HEALTH_CHECK_INTERVAL_SECS: u64 = 30;
+pub const PROGRESS_INTERVAL_SECS: u64 = 60;
+pub const TX_BATCH_SIZE: u32 = 100;
+pub const BLOCK_BATCH_SIZE: u32 = 10;
+pub const NGRAM_SIZE: usize = 2;
 pub const DB_PATH: &str = "sqlite://statistics.sqlite";
+pub const CONFIG_PATH: &str = "config.toml";
 
 // -- sled db constants
 pub const SLED_DB_PATH: &str = "data";
@@ -10,5 +16,7 @@ pub const METADATA_TREE: &str = "metadata";
 pub const TX_CONTRACT_ADDRESS_TREE: &str = "tx_contract_address";
 pub const INIT_CODE_TREE: &str = "init_code";
 pub const CONTRACT_TREE: &str = "contract";
+pub const TX_BLOCK_NUMBER_TREE: &str = "tx_block_number";
+pub const CORPUS_STAGING_TREE: &str = "corpus_staging";
 // sled key constants
 pub const LATEST_BLOCK_NUMBER: &str = "latest_block_number";
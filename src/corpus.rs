@@ -0,0 +1,335 @@
+//! Deduplicated corpus of the stored init and runtime codes.
+//!
+//! A corpus holds every code once under `init/<keccak>` and `runtime/<keccak>`, raw `.bin` or
+//! `.hex` text, next to an `index.jsonl` with one line per deployment tx. It is written as a
+//! directory or as a single `.tar.gz` archive of the same layout.
+
+use crate::consts::{
+    CONTRACT_TREE, CORPUS_STAGING_TREE, INIT_CODE_TREE, TX_BLOCK_NUMBER_TREE,
+    TX_CONTRACT_ADDRESS_TREE,
+};
+use crate::db::get_tx_block_number;
+use ethers::types::{Address, H256};
+use ethers::utils::keccak256;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+const INDEX_FILE: &str = "index.jsonl";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub tx_hash: H256,
+    /// Unknown for txs not analyzed yet.
+    pub address: Option<Address>,
+    /// Unknown for txs stored before block numbers were recorded.
+    pub block_number: Option<u64>,
+    pub init_code_hash: H256,
+    pub init_code_size: usize,
+    /// Unknown for failed deployments and txs not analyzed yet.
+    pub code_hash: Option<H256>,
+    pub code_size: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Encoding {
+    #[default]
+    Bin,
+    Hex,
+}
+
+impl Encoding {
+    fn extension(self) -> &'static str {
+        match self {
+            Encoding::Bin => "bin",
+            Encoding::Hex => "hex",
+        }
+    }
+}
+
+/// The sled trees a corpus is read from and written to.
+pub struct Trees {
+    db: sled::Db,
+    init_code: sled::Tree,
+    tx_contract_address: sled::Tree,
+    contract: sled::Tree,
+    tx_block_number: sled::Tree,
+}
+
+impl Trees {
+    pub fn open(db: &sled::Db) -> sled::Result<Self> {
+        Ok(Self {
+            db: db.clone(),
+            init_code: db.open_tree(INIT_CODE_TREE)?,
+            tx_contract_address: db.open_tree(TX_CONTRACT_ADDRESS_TREE)?,
+            contract: db.open_tree(CONTRACT_TREE)?,
+            tx_block_number: db.open_tree(TX_BLOCK_NUMBER_TREE)?,
+        })
+    }
+}
+
+enum Sink {
+    Dir(PathBuf),
+    Archive(tar::Builder<GzEncoder<File>>),
+}
+
+impl Sink {
+    fn add(&mut self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Sink::Dir(dir) => {
+                let path = dir.join(path);
+                std::fs::create_dir_all(path.parent().unwrap())?;
+                std::fs::write(path, data)?;
+            }
+            Sink::Archive(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header, path, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if let Sink::Archive(builder) = self {
+            builder.into_inner()?.finish()?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CorpusSummary {
+    pub deployments: usize,
+    pub init_codes: usize,
+    pub runtime_codes: usize,
+}
+
+/// Write the corpus to the directory `out`, or to the `.tar.gz` file `out` if `archive` is set.
+pub fn export_corpus(
+    trees: &Trees,
+    out: &Path,
+    archive: bool,
+    encoding: Encoding,
+) -> anyhow::Result<CorpusSummary> {
+    let mut sink = if archive {
+        let file = File::create(out)?;
+        Sink::Archive(tar::Builder::new(GzEncoder::new(
+            file,
+            Compression::default(),
+        )))
+    } else {
+        std::fs::create_dir_all(out)?;
+        Sink::Dir(out.to_path_buf())
+    };
+
+    // the index is streamed to a file, an archive takes it as its last entry once complete
+    let index_path = match &sink {
+        Sink::Dir(dir) => dir.join(INDEX_FILE),
+        Sink::Archive(_) => PathBuf::from(format!("{}.{}.tmp", out.display(), INDEX_FILE)),
+    };
+    let mut index = BufWriter::new(File::create(&index_path)?);
+
+    let mut summary = CorpusSummary::default();
+    let (mut init_written, mut runtime_written) = (HashSet::new(), HashSet::new());
+    for item in trees.init_code.iter() {
+        let (tx_hash, init_code) = item?;
+        let init_code_hash = H256::from(keccak256(&init_code));
+        if init_written.insert(init_code_hash) {
+            sink.add(
+                &code_path("init", init_code_hash, encoding),
+                &encode(&init_code, encoding),
+            )?;
+            summary.init_codes += 1;
+        }
+
+        let address = trees
+            .tx_contract_address
+            .get(&tx_hash)?
+            .map(|address| Address::from_slice(&address));
+        let code = match address {
+            Some(address) => trees.contract.get(address.as_bytes())?,
+            None => None,
+        };
+        let code_hash = code.as_ref().map(|code| H256::from(keccak256(code)));
+        if let (Some(code), Some(code_hash)) = (&code, code_hash) {
+            if runtime_written.insert(code_hash) {
+                sink.add(
+                    &code_path("runtime", code_hash, encoding),
+                    &encode(code, encoding),
+                )?;
+                summary.runtime_codes += 1;
+            }
+        }
+
+        let entry = IndexEntry {
+            tx_hash: H256::from_slice(&tx_hash),
+            address,
+            block_number: get_tx_block_number(&trees.tx_block_number, &tx_hash)?,
+            init_code_hash,
+            init_code_size: init_code.len(),
+            code_hash,
+            code_size: code.map(|code| code.len()),
+        };
+        serde_json::to_writer(&mut index, &entry)?;
+        index.write_all(b"\n")?;
+        summary.deployments += 1;
+    }
+    index.into_inner()?.sync_all()?;
+    if let Sink::Archive(builder) = &mut sink {
+        builder.append_path_with_name(&index_path, INDEX_FILE)?;
+        std::fs::remove_file(&index_path)?;
+    }
+    sink.finish()?;
+    Ok(summary)
+}
+
+/// Read a corpus directory or `.tar.gz` archive back into the sled trees.
+///
+/// Existing entries are overwritten, codes are checked against their hashes. An archive is read
+/// twice: its codes are staged in a temporary tree as they are read, then the index is read and
+/// looked up in it, so neither is held in memory.
+pub fn import_corpus(trees: &Trees, input: &Path) -> anyhow::Result<CorpusSummary> {
+    if input.is_dir() {
+        let index = File::open(input.join(INDEX_FILE))?;
+        return import_index(
+            trees,
+            BufReader::new(index),
+            &Source::Dir(input.to_path_buf()),
+        );
+    }
+
+    let staging = trees.db.open_tree(CORPUS_STAGING_TREE)?;
+    staging.clear()?;
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(input)?));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        if !entry.header().entry_type().is_file() || path == Path::new(INDEX_FILE) {
+            continue;
+        }
+        let (Some(kind), Some(stem)) = (path.parent(), path.file_stem()) else {
+            continue;
+        };
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+        if path.extension().is_some_and(|extension| extension == "hex") {
+            data = hex::decode(String::from_utf8(data)?.trim())?;
+        }
+        let key = format!("{}/{}", kind.display(), stem.to_string_lossy());
+        staging.insert(key, data)?;
+    }
+
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(input)?));
+    let mut summary = None;
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if entry.path()? == Path::new(INDEX_FILE) {
+            summary = Some(import_index(
+                trees,
+                BufReader::new(entry),
+                &Source::Staged(staging.clone()),
+            )?);
+            break;
+        }
+    }
+    trees.db.drop_tree(CORPUS_STAGING_TREE)?;
+    summary.ok_or_else(|| anyhow::anyhow!("{INDEX_FILE} missing in archive"))
+}
+
+fn import_index(
+    trees: &Trees,
+    index: impl BufRead,
+    source: &Source,
+) -> anyhow::Result<CorpusSummary> {
+    let mut summary = CorpusSummary::default();
+    let (mut init_codes, mut runtime_codes) = (HashSet::new(), HashSet::new());
+    for line in index.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: IndexEntry = serde_json::from_str(&line)?;
+        let tx_hash = entry.tx_hash.as_bytes();
+        let init_code = source.read_code("init", entry.init_code_hash)?;
+        trees.init_code.insert(tx_hash, init_code)?;
+        if let Some(block_number) = entry.block_number {
+            trees
+                .tx_block_number
+                .insert(tx_hash, &block_number.to_be_bytes())?;
+        }
+        if let Some(address) = entry.address {
+            trees
+                .tx_contract_address
+                .insert(tx_hash, address.as_bytes())?;
+            if let Some(code_hash) = entry.code_hash {
+                let code = source.read_code("runtime", code_hash)?;
+                trees.contract.insert(address.as_bytes(), code)?;
+                runtime_codes.insert(code_hash);
+            }
+        }
+        init_codes.insert(entry.init_code_hash);
+        summary.deployments += 1;
+    }
+    summary.init_codes = init_codes.len();
+    summary.runtime_codes = runtime_codes.len();
+    trees.init_code.flush()?;
+    Ok(summary)
+}
+
+enum Source {
+    Dir(PathBuf),
+    /// Decoded archive codes by `<kind>/<keccak>`, archives can't be read out of order.
+    Staged(sled::Tree),
+}
+
+impl Source {
+    /// Read a code in either encoding and check its hash.
+    fn read_code(&self, kind: &str, hash: H256) -> anyhow::Result<Vec<u8>> {
+        let code = match self {
+            Source::Dir(dir) => {
+                let bin = dir.join(code_path(kind, hash, Encoding::Bin));
+                if bin.exists() {
+                    std::fs::read(bin)?
+                } else {
+                    let text = std::fs::read(dir.join(code_path(kind, hash, Encoding::Hex)))?;
+                    hex::decode(String::from_utf8(text)?.trim())?
+                }
+            }
+            Source::Staged(staging) => {
+                let key = format!("{}/{}", kind, hex::encode(hash.as_bytes()));
+                staging
+                    .get(&key)?
+                    .ok_or_else(|| anyhow::anyhow!("{key} missing in archive"))?
+                    .to_vec()
+            }
+        };
+        if H256::from(keccak256(&code)) != hash {
+            anyhow::bail!("{kind} code {hash:?} does not match its hash");
+        }
+        Ok(code)
+    }
+}
+
+fn code_path(kind: &str, hash: H256, encoding: Encoding) -> String {
+    format!(
+        "{}/{}.{}",
+        kind,
+        hex::encode(hash.as_bytes()),
+        encoding.extension()
+    )
+}
+
+fn encode(code: &[u8], encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Bin => code.to_vec(),
+        Encoding::Hex => hex::encode(code).into_bytes(),
+    }
+}
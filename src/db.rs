@@ -1,6 +1,8 @@
-use crate::consts::{DB_PATH, LATEST_BLOCK_NUMBER, SHANGHAI_FORK};
+use crate::consts::{DB_PATH, LATEST_BLOCK_NUMBER};
+use crate::evm::OpcodeId;
 use ethers::prelude::*;
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::collections::HashMap;
 
 pub async fn init_sqlite() -> Result<SqlitePool, sqlx::Error> {
     let pool = SqlitePoolOptions::new()
@@ -24,10 +26,28 @@ pub async fn init_sqlite() -> Result<SqlitePool, sqlx::Error> {
     Ok(pool)
 }
 
-pub fn get_latest_recorded_block(tree: &sled::Tree) -> Result<u64, sled::Error> {
+/// Fresh in-memory database with the migrations applied.
+#[cfg(test)]
+pub async fn memory_sqlite() -> SqlitePool {
+    // every connection opens its own in-memory database, so keep exactly one alive
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!().run(&pool).await.unwrap();
+    pool
+}
+
+/// Latest block submitted as a task, the one before `start_block` if none was yet.
+///
+/// Genesis has no txs, so starting at 0 skips nothing.
+pub fn get_latest_recorded_block(tree: &sled::Tree, start_block: u64) -> Result<u64, sled::Error> {
     tree.get(LATEST_BLOCK_NUMBER).map(|r| {
         r.and_then(|v| bincode::deserialize(&v).ok())
-            .unwrap_or(SHANGHAI_FORK - 1)
+            .unwrap_or(start_block.saturating_sub(1))
     })
 }
 
@@ -39,10 +59,29 @@ pub fn set_latest_recorded_block(tree: &sled::Tree, block_number: u64) -> Result
     Ok(())
 }
 
-pub async fn submit_block_task(pool: &SqlitePool, block_number: u64) -> Result<(), sqlx::Error> {
+/// Block number of a deployment tx from the `tx_block_number` tree, `None` for txs stored
+/// before block numbers were recorded.
+pub fn get_tx_block_number(tree: &sled::Tree, tx_hash: &[u8]) -> anyhow::Result<Option<u64>> {
+    tree.get(tx_hash)?
+        .map(|n| {
+            let n = n.as_ref().try_into().map_err(|_| {
+                anyhow::anyhow!("malformed block number of tx 0x{}", hex::encode(tx_hash))
+            })?;
+            Ok(u64::from_be_bytes(n))
+        })
+        .transpose()
+}
+
+pub async fn submit_block_task(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
     let block_number = block_number as i64;
     sqlx::query!(
-        "INSERT INTO block_tasks (block_number) VALUES (?) ON CONFLICT(block_number) DO NOTHING",
+        "INSERT INTO block_tasks (chain_id, block_number) VALUES (?, ?) ON CONFLICT(chain_id, block_number) DO NOTHING",
+        chain_id,
         block_number,
     )
     .execute(pool)
@@ -50,10 +89,16 @@ pub async fn submit_block_task(pool: &SqlitePool, block_number: u64) -> Result<(
     Ok(())
 }
 
-pub async fn submit_tx_task(pool: &SqlitePool, tx_hash: H256) -> Result<(), sqlx::Error> {
+pub async fn submit_tx_task(
+    pool: &SqlitePool,
+    chain_id: u64,
+    tx_hash: H256,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
     let hash = tx_hash.as_ref();
     sqlx::query!(
-        "INSERT INTO tx_tasks (tx_hash) VALUES (?) ON CONFLICT(tx_hash) DO NOTHING",
+        "INSERT INTO tx_tasks (chain_id, tx_hash) VALUES (?, ?) ON CONFLICT(chain_id, tx_hash) DO NOTHING",
+        chain_id,
         hash,
     )
     .execute(pool)
@@ -61,31 +106,65 @@ pub async fn submit_tx_task(pool: &SqlitePool, tx_hash: H256) -> Result<(), sqlx
     Ok(())
 }
 
+pub async fn submit_trace_task(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let block_number = block_number as i64;
+    sqlx::query!(
+        "INSERT INTO trace_tasks (chain_id, block_number) VALUES (?, ?) ON CONFLICT(chain_id, block_number) DO NOTHING",
+        chain_id,
+        block_number,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 pub struct BlockTaskGuard<'a> {
     pool: &'a SqlitePool,
+    chain_id: u64,
     block_number: u64,
     finished: bool,
 }
 
 impl<'a> BlockTaskGuard<'a> {
-    pub async fn new(pool: &'a SqlitePool) -> Result<Option<BlockTaskGuard<'a>>, sqlx::Error> {
-        Ok(sqlx::query!(
+    /// Take up to `limit` of the lowest block tasks at once.
+    pub async fn new_batch(
+        pool: &'a SqlitePool,
+        chain_id: u64,
+        limit: u32,
+    ) -> Result<Vec<BlockTaskGuard<'a>>, sqlx::Error> {
+        let id = chain_id as i64;
+        let limit = limit as i64;
+        let mut guards = sqlx::query!(
             r#"DELETE FROM block_tasks
-            WHERE block_number = (
+            WHERE chain_id = ? AND block_number IN (
                 SELECT block_number
                 FROM block_tasks
+                WHERE chain_id = ?
                 ORDER BY block_number ASC
-                LIMIT 1
+                LIMIT ?
             )
-            RETURNING block_number"#
+            RETURNING block_number"#,
+            id,
+            id,
+            limit,
         )
-        .fetch_optional(pool)
+        .fetch_all(pool)
         .await?
+        .into_iter()
         .map(|r| Self {
             pool,
+            chain_id,
             block_number: r.block_number as u64,
             finished: false,
-        }))
+        })
+        .collect::<Vec<_>>();
+        guards.sort_by_key(|g| g.block_number);
+        Ok(guards)
     }
 
     pub fn block_number(&self) -> u64 {
@@ -101,9 +180,9 @@ impl<'a> Drop for BlockTaskGuard<'a> {
     fn drop(&mut self) {
         if !self.finished {
             let pool = self.pool.clone();
-            let block_number = self.block_number;
+            let (chain_id, block_number) = (self.chain_id, self.block_number);
             tokio::spawn(async move {
-                if let Err(e) = submit_block_task(&pool, block_number).await {
+                if let Err(e) = submit_block_task(&pool, chain_id, block_number).await {
                     error!("failed to re-submit block task: {}", e);
                 }
             });
@@ -113,29 +192,44 @@ impl<'a> Drop for BlockTaskGuard<'a> {
 
 pub struct TxTaskGuard<'a> {
     pool: &'a SqlitePool,
+    chain_id: u64,
     tx_hash: H256,
     finished: bool,
 }
 
 impl<'a> TxTaskGuard<'a> {
-    pub async fn new(pool: &'a SqlitePool) -> Result<Option<TxTaskGuard<'a>>, sqlx::Error> {
+    /// Take up to `limit` tx tasks at once.
+    pub async fn new_batch(
+        pool: &'a SqlitePool,
+        chain_id: u64,
+        limit: u32,
+    ) -> Result<Vec<TxTaskGuard<'a>>, sqlx::Error> {
+        let id = chain_id as i64;
+        let limit = limit as i64;
         Ok(sqlx::query!(
             r#"DELETE FROM tx_tasks
-            WHERE tx_hash = (
+            WHERE chain_id = ? AND tx_hash IN (
                 SELECT tx_hash
                 FROM tx_tasks
-                LIMIT 1
+                WHERE chain_id = ?
+                LIMIT ?
             )
             RETURNING tx_hash
-            "#
+            "#,
+            id,
+            id,
+            limit,
         )
-        .fetch_optional(pool)
+        .fetch_all(pool)
         .await?
+        .into_iter()
         .map(|r| Self {
             pool,
+            chain_id,
             tx_hash: H256::from_slice(&r.tx_hash),
             finished: false,
-        }))
+        })
+        .collect())
     }
 
     pub fn tx_hash(&self) -> H256 {
@@ -151,9 +245,9 @@ impl<'a> Drop for TxTaskGuard<'a> {
     fn drop(&mut self) {
         if !self.finished {
             let pool = self.pool.clone();
-            let hash = self.tx_hash;
+            let (chain_id, hash) = (self.chain_id, self.tx_hash);
             tokio::spawn(async move {
-                if let Err(e) = submit_tx_task(&pool, hash).await {
+                if let Err(e) = submit_tx_task(&pool, chain_id, hash).await {
                     error!("failed to re-submit tx task: {}", e);
                 }
             });
@@ -161,17 +255,79 @@ impl<'a> Drop for TxTaskGuard<'a> {
     }
 }
 
+pub struct TraceTaskGuard<'a> {
+    pool: &'a SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+    finished: bool,
+}
+
+impl<'a> TraceTaskGuard<'a> {
+    pub async fn new(
+        pool: &'a SqlitePool,
+        chain_id: u64,
+    ) -> Result<Option<TraceTaskGuard<'a>>, sqlx::Error> {
+        let id = chain_id as i64;
+        Ok(sqlx::query!(
+            r#"DELETE FROM trace_tasks
+            WHERE chain_id = ? AND block_number = (
+                SELECT block_number
+                FROM trace_tasks
+                WHERE chain_id = ?
+                ORDER BY block_number ASC
+                LIMIT 1
+            )
+            RETURNING block_number"#,
+            id,
+            id,
+        )
+        .fetch_optional(pool)
+        .await?
+        .map(|r| Self {
+            pool,
+            chain_id,
+            block_number: r.block_number as u64,
+            finished: false,
+        }))
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.block_number
+    }
+
+    pub fn complete(mut self) {
+        self.finished = true;
+    }
+}
+
+impl<'a> Drop for TraceTaskGuard<'a> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let pool = self.pool.clone();
+            let (chain_id, block_number) = (self.chain_id, self.block_number);
+            tokio::spawn(async move {
+                if let Err(e) = submit_trace_task(&pool, chain_id, block_number).await {
+                    error!("failed to re-submit trace task: {}", e);
+                }
+            });
+        }
+    }
+}
+
 pub async fn append_opcode_statistics(
     pool: &SqlitePool,
+    chain_id: u64,
     block_number: u64,
     opcode: u8,
     count: u64,
 ) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
     let block_number = block_number as i64;
     let opcode = opcode as i64;
     let count = count as i64;
     sqlx::query!(
-        "INSERT INTO opcode_statistics (block_number, opcode, count) VALUES (?, ?, ?) ON CONFLICT(block_number, opcode) DO UPDATE SET count = count + ?",
+        "INSERT INTO opcode_statistics (chain_id, block_number, opcode, count) VALUES (?, ?, ?, ?) ON CONFLICT(chain_id, block_number, opcode) DO UPDATE SET count = count + ?",
+        chain_id,
         block_number,
         opcode,
         count,
@@ -181,3 +337,335 @@ pub async fn append_opcode_statistics(
         .await?;
     Ok(())
 }
+
+pub async fn append_eof_opcode_statistics(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+    opcode: u8,
+    count: u64,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let block_number = block_number as i64;
+    let opcode = opcode as i64;
+    let count = count as i64;
+    sqlx::query!(
+        "INSERT INTO eof_opcode_statistics (chain_id, block_number, opcode, count) VALUES (?, ?, ?, ?) ON CONFLICT(chain_id, block_number, opcode) DO UPDATE SET count = count + ?",
+        chain_id,
+        block_number,
+        opcode,
+        count,
+        count,
+    )
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn append_executed_opcode_statistics(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+    opcode: u8,
+    count: u64,
+    gas: u64,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let block_number = block_number as i64;
+    let opcode = opcode as i64;
+    let count = count as i64;
+    let gas = gas as i64;
+    sqlx::query!(
+        "INSERT INTO executed_opcode_statistics (chain_id, block_number, opcode, count, gas) VALUES (?, ?, ?, ?, ?) ON CONFLICT(chain_id, block_number, opcode) DO UPDATE SET count = count + ?, gas = gas + ?",
+        chain_id,
+        block_number,
+        opcode,
+        count,
+        gas,
+        count,
+        gas,
+    )
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn append_contract_calls(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+    address: Address,
+    count: u64,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let block_number = block_number as i64;
+    let address = address.as_bytes();
+    let count = count as i64;
+    sqlx::query!(
+        "INSERT INTO contract_calls (chain_id, block_number, address, count) VALUES (?, ?, ?, ?) ON CONFLICT(chain_id, block_number, address) DO UPDATE SET count = count + ?",
+        chain_id,
+        block_number,
+        address,
+        count,
+        count,
+    )
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Replace the opcode histogram of a contract, e.g. after it was redeployed to the same address,
+/// into `contract_eof_opcode_statistics` if `eof` is set.
+pub async fn set_contract_opcode_statistics(
+    pool: &SqlitePool,
+    chain_id: u64,
+    address: Address,
+    counts: &[(u8, u64)],
+    eof: bool,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let address = address.as_bytes();
+    let mut tx = pool.begin().await?;
+    // the previous code may have been of the other kind
+    sqlx::query!(
+        "DELETE FROM contract_opcode_statistics WHERE chain_id = ? AND address = ?",
+        chain_id,
+        address
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM contract_eof_opcode_statistics WHERE chain_id = ? AND address = ?",
+        chain_id,
+        address
+    )
+    .execute(&mut *tx)
+    .await?;
+    for (opcode, count) in counts {
+        let opcode = *opcode as i64;
+        let count = *count as i64;
+        if eof {
+            sqlx::query!(
+                "INSERT INTO contract_eof_opcode_statistics (chain_id, address, opcode, count) VALUES (?, ?, ?, ?)",
+                chain_id,
+                address,
+                opcode,
+                count,
+            )
+            .execute(&mut *tx)
+            .await?;
+        } else {
+            sqlx::query!(
+                "INSERT INTO contract_opcode_statistics (chain_id, address, opcode, count) VALUES (?, ?, ?, ?)",
+                chain_id,
+                address,
+                opcode,
+                count,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    tx.commit().await
+}
+
+pub async fn set_block_timestamp(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+    timestamp: u64,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let block_number = block_number as i64;
+    let timestamp = timestamp as i64;
+    sqlx::query!(
+        "INSERT INTO blocks (chain_id, block_number, timestamp) VALUES (?, ?, ?) ON CONFLICT(chain_id, block_number) DO UPDATE SET timestamp = ?",
+        chain_id,
+        block_number,
+        timestamp,
+        timestamp,
+    )
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn get_block_timestamp(
+    pool: &SqlitePool,
+    chain_id: u64,
+    block_number: u64,
+) -> Result<Option<u64>, sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let block_number = block_number as i64;
+    Ok(sqlx::query!(
+        "SELECT timestamp FROM blocks WHERE chain_id = ? AND block_number = ?",
+        chain_id,
+        block_number
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|r| r.timestamp as u64))
+}
+
+/// Recorded timestamps of all blocks of the chain, by block number.
+pub async fn get_block_timestamps(
+    pool: &SqlitePool,
+    chain_id: u64,
+) -> Result<HashMap<u64, u64>, sqlx::Error> {
+    let chain_id = chain_id as i64;
+    Ok(sqlx::query!(
+        "SELECT block_number, timestamp FROM blocks WHERE chain_id = ?",
+        chain_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| (r.block_number as u64, r.timestamp as u64))
+    .collect())
+}
+
+/// First recorded block with a timestamp at or after `timestamp`.
+pub async fn get_first_block_at(
+    pool: &SqlitePool,
+    chain_id: u64,
+    timestamp: u64,
+) -> Result<Option<u64>, sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let timestamp = timestamp as i64;
+    Ok(sqlx::query!(
+        "SELECT MIN(block_number) AS block_number FROM blocks WHERE chain_id = ? AND timestamp >= ?",
+        chain_id,
+        timestamp
+    )
+    .fetch_one(pool)
+    .await?
+    .block_number
+    .map(|n| n as u64))
+}
+
+/// Opcode histogram of a contract, decoded as EOF for EOF contracts.
+pub async fn get_contract_opcode_statistics(
+    pool: &SqlitePool,
+    chain_id: u64,
+    address: Address,
+) -> Result<Vec<(OpcodeId, u64)>, sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let address = address.as_bytes();
+    let counts = sqlx::query!(
+        "SELECT opcode, count FROM contract_opcode_statistics WHERE chain_id = ? AND address = ? ORDER BY count DESC",
+        chain_id,
+        address
+    )
+    .fetch_all(pool)
+    .await?;
+    if !counts.is_empty() {
+        return Ok(counts
+            .into_iter()
+            .map(|r| (OpcodeId::from(r.opcode as u8), r.count as u64))
+            .collect());
+    }
+    Ok(sqlx::query!(
+        "SELECT opcode, count FROM contract_eof_opcode_statistics WHERE chain_id = ? AND address = ? ORDER BY count DESC",
+        chain_id,
+        address
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| (OpcodeId::from_eof(r.opcode as u8), r.count as u64))
+    .collect())
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Progress {
+    /// Latest block with a recorded timestamp, i.e. fetched by a block worker.
+    pub latest_processed_block: Option<u64>,
+    pub block_tasks: u64,
+    pub tx_tasks: u64,
+    pub trace_tasks: u64,
+}
+
+pub async fn get_progress(pool: &SqlitePool, chain_id: u64) -> Result<Progress, sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let r = sqlx::query!(
+        r#"SELECT
+            (SELECT MAX(block_number) FROM blocks WHERE chain_id = ?1) AS "latest_processed_block: i64",
+            (SELECT COUNT(*) FROM block_tasks WHERE chain_id = ?1) AS "block_tasks!",
+            (SELECT COUNT(*) FROM tx_tasks WHERE chain_id = ?1) AS "tx_tasks!",
+            (SELECT COUNT(*) FROM trace_tasks WHERE chain_id = ?1) AS "trace_tasks!"
+        "#,
+        chain_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(Progress {
+        latest_processed_block: r.latest_processed_block.map(|n| n as u64),
+        block_tasks: r.block_tasks as u64,
+        tx_tasks: r.tx_tasks as u64,
+        trace_tasks: r.trace_tasks as u64,
+    })
+}
+
+/// Throughput measured by the running scanner, persisted for the `status` command.
+#[derive(Clone, Debug)]
+pub struct ScanRates {
+    pub head_block: Option<u64>,
+    pub blocks_per_second: f64,
+    pub tx_tasks_per_second: f64,
+    /// Unix timestamp of the measurement.
+    pub updated_at: u64,
+}
+
+pub async fn set_scan_rates(
+    pool: &SqlitePool,
+    chain_id: u64,
+    rates: &ScanRates,
+) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    let head_block = rates.head_block.map(|n| n as i64);
+    let updated_at = rates.updated_at as i64;
+    sqlx::query!(
+        "INSERT OR REPLACE INTO scan_rates (chain_id, head_block, blocks_per_second, tx_tasks_per_second, updated_at) VALUES (?, ?, ?, ?, ?)",
+        chain_id,
+        head_block,
+        rates.blocks_per_second,
+        rates.tx_tasks_per_second,
+        updated_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_scan_rates(
+    pool: &SqlitePool,
+    chain_id: u64,
+) -> Result<Option<ScanRates>, sqlx::Error> {
+    let chain_id = chain_id as i64;
+    Ok(sqlx::query!(
+        "SELECT head_block, blocks_per_second, tx_tasks_per_second, updated_at FROM scan_rates WHERE chain_id = ?",
+        chain_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|r| ScanRates {
+        head_block: r.head_block.map(|n| n as u64),
+        blocks_per_second: r.blocks_per_second,
+        tx_tasks_per_second: r.tx_tasks_per_second,
+        updated_at: r.updated_at as u64,
+    }))
+}
+
+pub async fn clear_opcode_statistics(pool: &SqlitePool, chain_id: u64) -> Result<(), sqlx::Error> {
+    let chain_id = chain_id as i64;
+    sqlx::query!("DELETE FROM opcode_statistics WHERE chain_id = ?", chain_id)
+        .execute(pool)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM eof_opcode_statistics WHERE chain_id = ?",
+        chain_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,5 +1,13 @@
+mod asm;
 mod bytecode;
+mod disasm;
+pub mod eof;
 mod opcode;
+pub mod stack;
+mod view;
 
-pub use bytecode::{Bytecode, BytecodeElement};
+pub use asm::assemble;
+pub use bytecode::Bytecode;
+pub use disasm::disassemble;
 pub use opcode::OpcodeId;
+pub use view::{BytecodeView, CodeBitmap, Instructions, StaticJump};
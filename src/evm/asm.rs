@@ -0,0 +1,291 @@
+//! EVM assembler
+//!
+//! Source has one instruction or label per line, `;` starts a comment:
+//!
+//! ```text
+//! PUSH 0x80        ; narrowest PUSHn that fits
+//! PUSH2 1          ; explicit width, zero padded
+//! loop:            ; label, place a JUMPDEST after it to jump here
+//!     JUMPDEST
+//!     PUSH loop    ; offset of the label
+//!     JUMP
+//! ```
+
+use crate::evm::bytecode::push_data;
+use crate::evm::opcode::OpcodeId;
+use crate::evm::Bytecode;
+use ethers::types::U256;
+use std::collections::HashMap;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AsmError {
+    #[error("line {0}: unknown opcode {1}")]
+    UnknownOpcode(usize, String),
+    #[error("line {0}: invalid operand {1}")]
+    InvalidOperand(usize, String),
+    #[error("line {0}: {1} takes no operand")]
+    UnexpectedOperand(usize, String),
+    #[error("line {0}: {1} needs an operand")]
+    MissingOperand(usize, String),
+    #[error("line {0}: value does not fit in {1}")]
+    Overflow(usize, String),
+    #[error("line {0}: {1} is only defined in EOF code")]
+    EofOnly(usize, String),
+    #[error("line {0}: label {1} defined twice")]
+    DuplicateLabel(usize, String),
+    #[error("line {0}: undefined label {1}")]
+    UndefinedLabel(usize, String),
+}
+
+enum Operand {
+    Value(U256),
+    Label(String),
+}
+
+enum Item {
+    Label(String),
+    Op(OpcodeId),
+    Push {
+        line: usize,
+        /// Data length of an explicit `PUSHn`, chosen from the operand for `PUSH`.
+        width: Option<usize>,
+        operand: Operand,
+    },
+}
+
+/// Assemble `source` into a [`Bytecode`].
+pub fn assemble(source: &str) -> Result<Bytecode, AsmError> {
+    let items = parse(source)?;
+
+    // Label offsets depend on the widths of the pushes before them and vice versa, so widths
+    // start at their minimum and only grow until the layout is stable.
+    let mut widths = items
+        .iter()
+        .map(|item| match item {
+            Item::Push {
+                width: Some(width), ..
+            } => *width,
+            Item::Push {
+                operand: Operand::Value(value),
+                ..
+            } => push_data(*value).len(),
+            _ => 0,
+        })
+        .collect::<Vec<_>>();
+    let labels = loop {
+        let mut labels = HashMap::new();
+        let mut offset = 0usize;
+        for (item, width) in items.iter().zip(widths.iter()) {
+            match item {
+                Item::Label(name) => {
+                    labels.insert(name.as_str(), offset);
+                }
+                Item::Op(_) => offset += 1,
+                Item::Push { .. } => offset += 1 + width,
+            }
+        }
+        let mut stable = true;
+        for (item, width) in items.iter().zip(widths.iter_mut()) {
+            if let Item::Push {
+                line,
+                width: None,
+                operand: Operand::Label(name),
+            } = item
+            {
+                let target = labels
+                    .get(name.as_str())
+                    .ok_or_else(|| AsmError::UndefinedLabel(*line, name.clone()))?;
+                let needed = push_data(U256::from(*target)).len();
+                if needed > *width {
+                    *width = needed;
+                    stable = false;
+                }
+            }
+        }
+        if stable {
+            break labels;
+        }
+    };
+
+    let mut code = Bytecode::default();
+    for (item, width) in items.iter().zip(widths) {
+        match item {
+            Item::Label(_) => {}
+            Item::Op(op) => {
+                code.write_op(*op);
+            }
+            Item::Push {
+                line,
+                width: explicit,
+                operand,
+            } => {
+                let value = match operand {
+                    Operand::Value(value) => *value,
+                    Operand::Label(name) => labels
+                        .get(name.as_str())
+                        .map(|offset| U256::from(*offset))
+                        .ok_or_else(|| AsmError::UndefinedLabel(*line, name.clone()))?,
+                };
+                if explicit.is_none() {
+                    // label widths settled on exactly what their offsets need
+                    code.push(value);
+                    continue;
+                }
+                let data = push_data(value);
+                if data.len() > width {
+                    return Err(AsmError::Overflow(*line, format!("PUSH{width}")));
+                }
+                let mut padded = vec![0; width - data.len()];
+                padded.extend(data);
+                code.write_push(&padded);
+            }
+        }
+    }
+    Ok(code)
+}
+
+fn parse(source: &str) -> Result<Vec<Item>, AsmError> {
+    let mut items = vec![];
+    let mut labels = HashMap::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.split(';').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_suffix(':') {
+            let name = name.trim();
+            if !is_label(name) {
+                return Err(AsmError::InvalidOperand(line_number, name.to_string()));
+            }
+            if labels.insert(name.to_string(), line_number).is_some() {
+                return Err(AsmError::DuplicateLabel(line_number, name.to_string()));
+            }
+            items.push(Item::Label(name.to_string()));
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let mnemonic = parts.next().unwrap().to_uppercase();
+        let operand = parts.next();
+        if let Some(extra) = parts.next() {
+            return Err(AsmError::InvalidOperand(line_number, extra.to_string()));
+        }
+        let width = if mnemonic == "PUSH" {
+            None
+        } else {
+            let op = mnemonic
+                .parse::<OpcodeId>()
+                .map_err(|_| AsmError::UnknownOpcode(line_number, mnemonic.clone()))?;
+            if op.is_eof_only() {
+                return Err(AsmError::EofOnly(line_number, mnemonic));
+            }
+            if !op.is_push_with_data() {
+                if operand.is_some() {
+                    return Err(AsmError::UnexpectedOperand(line_number, mnemonic));
+                }
+                items.push(Item::Op(op));
+                continue;
+            }
+            Some(op.postfix().unwrap() as usize)
+        };
+        let operand = operand.ok_or(AsmError::MissingOperand(line_number, mnemonic))?;
+        items.push(Item::Push {
+            line: line_number,
+            width,
+            operand: parse_operand(line_number, operand)?,
+        });
+    }
+    Ok(items)
+}
+
+fn parse_operand(line: usize, operand: &str) -> Result<Operand, AsmError> {
+    if is_label(operand) {
+        return Ok(Operand::Label(operand.to_string()));
+    }
+    let value = match operand.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(operand).ok(),
+    };
+    value
+        .map(Operand::Value)
+        .ok_or_else(|| AsmError::InvalidOperand(line, operand.to_string()))
+}
+
+fn is_label(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::disassemble;
+
+    #[test]
+    fn label_push_grows_to_push2() {
+        // the label needs a PUSH1 at 0xff, which moves it to 0x100 and so needs a PUSH2
+        let source = format!("PUSH end\n{}end:\nJUMPDEST\n", "CALLER\n".repeat(254));
+        let code = assemble(&source).unwrap().to_bytes();
+        assert_eq!(code[..3], [OpcodeId::PUSH2.as_u8(), 0x01, 0x01]);
+        assert_eq!(code.len(), 0x102);
+        assert_eq!(code[0x101], OpcodeId::JUMPDEST.as_u8());
+    }
+
+    #[test]
+    fn overflow() {
+        assert!(matches!(
+            assemble("PUSH1 0x100"),
+            Err(AsmError::Overflow(1, width)) if width == "PUSH1"
+        ));
+    }
+
+    #[test]
+    fn duplicate_label() {
+        assert!(matches!(
+            assemble("start:\nJUMPDEST\nstart:"),
+            Err(AsmError::DuplicateLabel(3, name)) if name == "start"
+        ));
+    }
+
+    #[test]
+    fn undefined_label() {
+        assert!(matches!(
+            assemble("PUSH nowhere\nJUMP"),
+            Err(AsmError::UndefinedLabel(1, name)) if name == "nowhere"
+        ));
+    }
+
+    #[test]
+    fn disassemble_round_trip() {
+        let source = "
+            PUSH 0x80        ; narrowest push
+            PUSH2 1          ; zero padded
+            PUSH 0
+            loop:
+                JUMPDEST
+                PUSH loop
+                JUMP
+        ";
+        let code = assemble(source).unwrap().to_bytes();
+        let disassembly = disassemble(&code);
+        let instructions = disassembly
+            .instructions
+            .iter()
+            .map(|i| (i.offset, i.opcode, i.immediate.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instructions,
+            [
+                (0, OpcodeId::PUSH1, vec![0x80]),
+                (2, OpcodeId::PUSH2, vec![0x00, 0x01]),
+                (5, OpcodeId::PUSH0, vec![]),
+                (6, OpcodeId::JUMPDEST, vec![]),
+                (7, OpcodeId::PUSH1, vec![0x06]),
+                (9, OpcodeId::JUMP, vec![]),
+            ]
+        );
+        assert_eq!(disassembly.metadata_offset, None);
+        assert!(disassembly.to_string().contains("PUSH1 0x06 ; label_0006"));
+    }
+}
@@ -1,7 +1,10 @@
 //! EVM byte code generator
 
+use crate::evm::eof::{is_eof, EofContainer, EofError};
 use crate::evm::opcode::OpcodeId;
-use cbor::Decoder as CborDecoder;
+use crate::evm::view::Instructions;
+use ethers::types::U256;
+use std::ops::Range;
 
 /// Helper struct that represents a single element in a bytecode.
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
@@ -13,11 +16,17 @@ pub struct BytecodeElement {
 }
 
 /// EVM Bytecode
+///
+/// Decoding keeps every input byte, so [`Bytecode::to_bytes`] returns exactly the decoded input.
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Bytecode {
-    /// Vector for bytecode elements.
-    pub code: Vec<BytecodeElement>,
+    /// Raw bytes of the code, without metadata.
+    code: Vec<u8>,
+    /// Whether each byte of `code` is an opcode rather than push data.
+    is_code: Vec<bool>,
     num_opcodes: usize,
+    /// Trailing CBOR metadata, not part of `code`.
+    metadata: Vec<u8>,
 }
 
 impl Bytecode {
@@ -33,15 +42,89 @@ impl Bytecode {
 
     /// Write byte
     pub fn write(&mut self, value: u8, is_code: bool) -> &mut Self {
-        self.code.push(BytecodeElement { value, is_code });
+        self.code.push(value);
+        self.is_code.push(is_code);
         self
     }
+
+    /// Write `PUSHn` with `n` the length of `data`, followed by `data`
+    pub fn write_push(&mut self, data: &[u8]) -> &mut Self {
+        assert!(data.len() <= 32, "push data longer than 32 bytes");
+        self.write_op_internal(OpcodeId::PUSH0.as_u8() + data.len() as u8);
+        for byte in data {
+            self.write(*byte, false);
+        }
+        self
+    }
+
+    /// Write a push of `value` with the narrowest `PUSHn` that fits it
+    pub fn push(&mut self, value: impl Into<U256>) -> &mut Self {
+        self.write_push(&push_data(value.into()))
+    }
+
+    /// Raw bytes of the code, without metadata
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// Element at `pc`, `None` past the end of the code
+    pub fn element(&self, pc: usize) -> Option<BytecodeElement> {
+        let value = *self.code.get(pc)?;
+        Some(BytecodeElement {
+            value,
+            is_code: self.is_code[pc],
+        })
+    }
+
+    /// Elements of the code in order
+    pub fn elements(&self) -> impl ExactSizeIterator<Item = BytecodeElement> + '_ {
+        self.code
+            .iter()
+            .zip(&self.is_code)
+            .map(|(value, is_code)| BytecodeElement {
+                value: *value,
+                is_code: *is_code,
+            })
+    }
+
+    /// Encode back to raw bytes, metadata included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.code.len() + self.metadata.len());
+        bytes.extend_from_slice(&self.code);
+        bytes.extend_from_slice(&self.metadata);
+        bytes
+    }
+
+    /// Span of the metadata in [`Bytecode::to_bytes`], empty if there is none
+    pub fn metadata_span(&self) -> Range<usize> {
+        self.code.len()..self.code.len() + self.metadata.len()
+    }
+
+    /// Instructions as `(pc, opcode, immediate)`, the immediate is shorter than the push size
+    /// if the code ends early
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions::new(&self.code)
+    }
+
+    /// Whether `pc` is a `JUMPDEST` opcode rather than push data, and so a valid jump target
+    pub fn is_valid_jumpdest(&self, pc: usize) -> bool {
+        self.element(pc)
+            .is_some_and(|e| e.is_code && e.value == OpcodeId::JUMPDEST.as_u8())
+    }
+
+    /// The EOF container, `None` for legacy code
+    pub fn eof(&self) -> Option<Result<EofContainer<'_>, EofError>> {
+        is_eof(&self.code).then(|| EofContainer::parse(&self.code))
+    }
 }
 
 impl From<Vec<u8>> for Bytecode {
     fn from(mut input: Vec<u8>) -> Self {
-        trim_metadata(&mut input);
-        let mut code = Bytecode::default();
+        let metadata = input.split_off(input.len() - metadata_len(&input));
+        let mut code = Bytecode {
+            metadata,
+            ..Default::default()
+        };
 
         let mut input_iter = input.iter();
         while let Some(byte) = input_iter.next() {
@@ -68,24 +151,175 @@ impl From<Vec<u8>> for Bytecode {
     }
 }
 
-fn trim_metadata(bytecode: &mut Vec<u8>) {
-    if bytecode.len() <= 2 {
-        return;
+/// Big endian bytes of `value` without leading zeros, empty for 0 which is pushed by `PUSH0`.
+pub(crate) fn push_data(value: U256) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    let len = value.bits().div_ceil(8);
+    bytes[32 - len..].to_vec()
+}
+
+/// Length of the trailing CBOR metadata solc appends, including its 2 byte length, or 0 if the
+/// code does not end with valid CBOR.
+pub(crate) fn metadata_len(bytecode: &[u8]) -> usize {
+    // EOF containers keep metadata in the data section
+    if bytecode.len() <= 2 || is_eof(bytecode) {
+        return 0;
     }
     // cbor length is last 2 bytes of bytecode, u16 big endian
     let cbor_length =
         u16::from_be_bytes([bytecode[bytecode.len() - 2], bytecode[bytecode.len() - 1]]) as usize;
     // if bytecode length is less than cbor length, it's not a valid cbor
     if bytecode.len() - 2 < cbor_length {
-        return;
-    }
-    let mut decode =
-        CborDecoder::from_bytes(&bytecode[bytecode.len() - 2 - cbor_length..bytecode.len() - 2]);
-    let mut items = decode.items();
-    while let Some(item) = items.next() {
-        if let Err(_) = item {
-            return;
+        return 0;
+    }
+    if !is_cbor(&bytecode[bytecode.len() - 2 - cbor_length..bytecode.len() - 2]) {
+        return 0;
+    }
+    cbor_length + 2
+}
+
+/// Whether `data` is a sequence of well formed CBOR items, checked without allocating so that
+/// arbitrary code cannot claim huge lengths. Indefinite lengths are rejected, solc does not emit
+/// them.
+fn is_cbor(data: &[u8]) -> bool {
+    let mut pos = 0;
+    // items still to read for the enclosing arrays, maps and tags
+    let mut pending = 0u64;
+    while pos < data.len() || pending > 0 {
+        let Some(&head) = data.get(pos) else {
+            return false;
+        };
+        pos += 1;
+        pending = pending.saturating_sub(1);
+        let (major, info) = (head >> 5, head & 0x1f);
+        let arg = match info {
+            0..=23 => info as u64,
+            24..=27 => {
+                let size = 1 << (info - 24);
+                let Some(bytes) = data.get(pos..pos + size) else {
+                    return false;
+                };
+                pos += size;
+                bytes.iter().fold(0, |acc, b| acc << 8 | *b as u64)
+            }
+            _ => return false,
+        };
+        let remaining = (data.len() - pos) as u64;
+        match major {
+            // byte and text strings
+            2 | 3 => {
+                if arg > remaining {
+                    return false;
+                }
+                pos += arg as usize;
+            }
+            // every item takes at least one byte
+            4..=6 => {
+                let items = match major {
+                    4 => arg,
+                    5 => arg.saturating_mul(2),
+                    _ => 1,
+                };
+                if items > remaining {
+                    return false;
+                }
+                pending += items;
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    /// CBOR solc 0.8.19 appends, `{"ipfs": <34 byte multihash>, "solc": <version>}`.
+    fn solc_cbor(digest: &[u8]) -> Vec<u8> {
+        let mut cbor = b"\xa2\x64ipfs\x58\x22\x12\x20".to_vec();
+        cbor.extend_from_slice(digest);
+        cbor.extend_from_slice(b"\x64solc\x43\x00\x08\x13");
+        cbor
+    }
+
+    /// CBOR vyper 0.3.7 appends, `{"vyper": [0, 3, 7]}`.
+    const VYPER_CBOR: &[u8] = b"\xa1\x65vyper\x83\x00\x03\x07";
+
+    fn with_length(cbor: &[u8]) -> Vec<u8> {
+        let mut tail = cbor.to_vec();
+        tail.extend_from_slice(&(cbor.len() as u16).to_be_bytes());
+        tail
+    }
+
+    #[test]
+    fn cbor_metadata() {
+        assert!(is_cbor(&solc_cbor(&[0xab; 32])));
+        assert!(is_cbor(VYPER_CBOR));
+    }
+
+    #[test]
+    fn truncated_cbor() {
+        let solc = solc_cbor(&[0xab; 32]);
+        assert!(!is_cbor(&solc[..solc.len() - 1]));
+        // map value missing
+        assert!(!is_cbor(&VYPER_CBOR[..7]));
+        // array item missing
+        assert!(!is_cbor(&VYPER_CBOR[..VYPER_CBOR.len() - 1]));
+        // length argument cut off
+        assert!(!is_cbor(b"\x19\x01"));
+    }
+
+    #[test]
+    fn malformed_cbor() {
+        // reserved additional info
+        assert!(!is_cbor(b"\x1c"));
+        // indefinite length array
+        assert!(!is_cbor(b"\x9f\x01\xff"));
+        // byte string longer than the data
+        assert!(!is_cbor(b"\x5a\xff\xff\xff\xff\x00"));
+        // map claiming more pairs than there are bytes
+        assert!(!is_cbor(b"\xbb\xff\xff\xff\xff\xff\xff\xff\xff"));
+    }
+
+    #[test]
+    fn metadata_tails() {
+        let mut code = vec![0x60, 0x80, 0x60, 0x40, 0x52];
+        let tail = with_length(&solc_cbor(&[0xab; 32]));
+        code.extend_from_slice(&tail);
+        assert_eq!(metadata_len(&code), tail.len());
+
+        let mut code = vec![0x60, 0x80, 0x60, 0x40, 0x52];
+        code.extend_from_slice(&with_length(VYPER_CBOR));
+        assert_eq!(metadata_len(&code), VYPER_CBOR.len() + 2);
+
+        // the length points before the start of the code
+        assert_eq!(metadata_len(&[0x00, 0x00, 0x40]), 0);
+        // the length covers bytes that are not CBOR
+        assert_eq!(metadata_len(&[0x60, 0x1c, 0x00, 0x01]), 0);
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(code in vec(any::<u8>(), 0..512)) {
+            prop_assert_eq!(Bytecode::from(code.clone()).to_bytes(), code);
+        }
+
+        #[test]
+        fn round_trip_with_metadata(
+            mut code in vec(any::<u8>(), 0..512),
+            digest in vec(any::<u8>(), 32),
+        ) {
+            let tail = with_length(&solc_cbor(&digest));
+            code.extend_from_slice(&tail);
+            let bytecode = Bytecode::from(code.clone());
+            prop_assert_eq!(bytecode.to_bytes(), code.clone());
+            if !is_eof(&code) {
+                prop_assert_eq!(bytecode.metadata_span(), code.len() - tail.len()..code.len());
+            }
         }
     }
-    bytecode.truncate(bytecode.len() - 2 - cbor_length);
 }
@@ -0,0 +1,263 @@
+//! EVM byte code disassembler
+
+use crate::evm::bytecode::Bytecode;
+use crate::evm::eof::{is_eof, EofContainer, EofError, TypeSection};
+use crate::evm::opcode::OpcodeId;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A decoded instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    /// Offset of the opcode in the code.
+    pub offset: usize,
+    pub opcode: OpcodeId,
+    /// Push data, shorter than the push size if the code ends early.
+    pub immediate: Vec<u8>,
+}
+
+impl Instruction {
+    /// Whether the code ended before all push data was read.
+    pub fn is_truncated(&self) -> bool {
+        self.opcode.is_push_with_data()
+            && self.immediate.len() < self.opcode.postfix().unwrap() as usize
+    }
+
+    /// Push data as a number, if it fits in a `usize`.
+    fn immediate_value(&self) -> Option<usize> {
+        if self.immediate.is_empty() || self.immediate.len() > std::mem::size_of::<usize>() {
+            return None;
+        }
+        Some(
+            self.immediate
+                .iter()
+                .fold(0, |acc, byte| (acc << 8) | *byte as usize),
+        )
+    }
+}
+
+/// Disassembled code, split into instructions and the trailing metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Disassembly {
+    /// Instructions of legacy code, or of an EOF container that does not parse.
+    pub instructions: Vec<Instruction>,
+    /// Offset of the CBOR metadata, if the code has any.
+    pub metadata_offset: Option<usize>,
+    /// The metadata including its 2 byte length.
+    pub metadata: Vec<u8>,
+    /// Sections of an EOF container, `None` for legacy code.
+    pub eof: Option<Result<EofDisassembly, EofError>>,
+}
+
+/// Sections of a disassembled EOF container.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EofDisassembly {
+    pub code_sections: Vec<EofCodeSection>,
+    /// Sizes of the subcontainers, which are not disassembled.
+    pub container_sizes: Vec<usize>,
+    pub data: Vec<u8>,
+}
+
+/// Disassembled EOF code section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EofCodeSection {
+    pub ty: TypeSection,
+    /// Offsets are relative to the section, as the targets of relative jumps are.
+    pub instructions: Vec<Instruction>,
+}
+
+impl Disassembly {
+    /// Offsets of the `JUMPDEST`s, the only valid jump targets.
+    pub fn jumpdests(&self) -> BTreeSet<usize> {
+        self.instructions
+            .iter()
+            .filter(|i| i.opcode == OpcodeId::JUMPDEST)
+            .map(|i| i.offset)
+            .collect()
+    }
+}
+
+pub fn disassemble(code: &[u8]) -> Disassembly {
+    if is_eof(code) {
+        match EofContainer::parse(code) {
+            Ok(container) => {
+                return Disassembly {
+                    eof: Some(Ok(disassemble_eof(&container))),
+                    ..Default::default()
+                }
+            }
+            Err(error) => {
+                return Disassembly {
+                    eof: Some(Err(error)),
+                    ..disassemble_legacy(code)
+                }
+            }
+        }
+    }
+    disassemble_legacy(code)
+}
+
+fn disassemble_legacy(code: &[u8]) -> Disassembly {
+    let bytecode = Bytecode::from(code.to_vec());
+    let instructions = bytecode
+        .instructions()
+        .map(|(offset, opcode, immediate)| Instruction {
+            offset,
+            opcode,
+            immediate: immediate.to_vec(),
+        })
+        .collect();
+    let metadata_span = bytecode.metadata_span();
+
+    Disassembly {
+        instructions,
+        metadata_offset: (!metadata_span.is_empty()).then_some(metadata_span.start),
+        metadata: code[metadata_span].to_vec(),
+        eof: None,
+    }
+}
+
+fn disassemble_eof(container: &EofContainer) -> EofDisassembly {
+    let code_sections = (0..container.code_sections.len())
+        .filter_map(|section| {
+            let instructions = container.instructions(section)?;
+            Some(EofCodeSection {
+                ty: container.types[section],
+                instructions: instructions
+                    .map(|(offset, opcode, immediate)| Instruction {
+                        offset,
+                        opcode,
+                        immediate: immediate.to_vec(),
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+    EofDisassembly {
+        code_sections,
+        container_sizes: container
+            .container_sections
+            .iter()
+            .map(|c| c.len())
+            .collect(),
+        data: container.data.to_vec(),
+    }
+}
+
+/// Name of an opcode in listings, undefined opcodes show their byte.
+pub fn mnemonic(opcode: OpcodeId) -> String {
+    match opcode {
+        OpcodeId::INVALID(0xfe) => "INVALID".to_string(),
+        OpcodeId::INVALID(b) => format!("INVALID(0x{b:02x})"),
+        _ => opcode.to_string(),
+    }
+}
+
+/// Listing with one instruction per line, `JUMPDEST` labels and pushed jump targets resolved.
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(Ok(eof)) = &self.eof {
+            for (index, section) in eof.code_sections.iter().enumerate() {
+                let outputs = if section.ty.is_returning() {
+                    format!("{} outputs", section.ty.outputs)
+                } else {
+                    "non-returning".to_string()
+                };
+                writeln!(
+                    f,
+                    "; code section {index}, {} inputs, {outputs}, max stack height {}",
+                    section.ty.inputs, section.ty.max_stack_height
+                )?;
+                for instruction in section.instructions.iter() {
+                    write_instruction(f, instruction, &BTreeSet::new())?;
+                }
+            }
+            for (index, size) in eof.container_sizes.iter().enumerate() {
+                writeln!(f, "; container section {index}, {size} bytes")?;
+            }
+            writeln!(f, "; data, {} bytes", eof.data.len())?;
+            for (i, chunk) in eof.data.chunks(32).enumerate() {
+                writeln!(f, "    {:04x}  {}", i * 32, hex::encode(chunk))?;
+            }
+            return Ok(());
+        }
+        if let Some(Err(error)) = &self.eof {
+            writeln!(f, "; invalid EOF container, {error}")?;
+        }
+        let jumpdests = self.jumpdests();
+        for instruction in self.instructions.iter() {
+            if instruction.opcode == OpcodeId::JUMPDEST {
+                writeln!(f, "label_{:04x}:", instruction.offset)?;
+            }
+            write_instruction(f, instruction, &jumpdests)?;
+        }
+        if let Some(offset) = self.metadata_offset {
+            writeln!(f, "; metadata, {} bytes", self.metadata.len())?;
+            for (i, chunk) in self.metadata.chunks(32).enumerate() {
+                writeln!(f, "    {:04x}  {}", offset + i * 32, hex::encode(chunk))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One listing line, pushed values that are in `jumpdests` resolved to their label.
+fn write_instruction(
+    f: &mut fmt::Formatter<'_>,
+    instruction: &Instruction,
+    jumpdests: &BTreeSet<usize>,
+) -> fmt::Result {
+    write!(
+        f,
+        "    {:04x}  {}",
+        instruction.offset,
+        mnemonic(instruction.opcode)
+    )?;
+    if !instruction.immediate.is_empty() {
+        write!(f, " 0x{}", hex::encode(&instruction.immediate))?;
+    }
+    if instruction.is_truncated() {
+        write!(f, " ; truncated")?;
+    } else if let Some(target) = instruction
+        .immediate_value()
+        .filter(|target| jumpdests.contains(target))
+    {
+        write!(f, " ; label_{target:04x}")?;
+    }
+    writeln!(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eof_sections() {
+        // one non-returning section `PUSH0 RJUMPI 0x0001 STOP STOP` and 2 bytes of data
+        let code = hex::decode(
+            "ef0001 010004 020001 0006 ff0002 00 00800001 5fe100010000 aabb".replace(' ', ""),
+        )
+        .unwrap();
+        let eof = disassemble(&code).eof.unwrap().unwrap();
+        let section = &eof.code_sections[0];
+        assert!(!section.ty.is_returning());
+        let opcodes = section.instructions.iter().map(|i| (i.offset, i.opcode));
+        assert_eq!(
+            opcodes.collect::<Vec<_>>(),
+            [
+                (0, OpcodeId::PUSH0),
+                (1, OpcodeId::RJUMPI),
+                (4, OpcodeId::STOP),
+                (5, OpcodeId::STOP)
+            ]
+        );
+        assert_eq!(eof.data, [0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn invalid_eof_as_legacy() {
+        let disassembly = disassemble(&[0xef, 0x00, 0x02]);
+        assert_eq!(disassembly.eof, Some(Err(EofError::UnsupportedVersion(2))));
+        assert_eq!(disassembly.instructions.len(), 3);
+    }
+}
@@ -0,0 +1,822 @@
+//! EOF containers
+//!
+//! Parsing and validation of EOFv1 as bundled by EIP-7692: the container format of EIP-3540 with
+//! the subcontainers of EIP-7620, and code validation of EIP-3670, EIP-4200, EIP-4750, EIP-5450,
+//! EIP-6206, EIP-663 and EIP-7480. Whether a subcontainer is used as initcode or runtime code is
+//! not checked against what it contains.
+
+use crate::evm::opcode::OpcodeId;
+use crate::evm::stack::STACK_LIMIT;
+use std::collections::BTreeSet;
+
+pub const EOF_MAGIC: [u8; 2] = [0xef, 0x00];
+pub const EOF_VERSION: u8 = 1;
+
+const KIND_TYPE: u8 = 0x01;
+const KIND_CODE: u8 = 0x02;
+const KIND_CONTAINER: u8 = 0x03;
+const KIND_DATA: u8 = 0xff;
+const TERMINATOR: u8 = 0x00;
+
+const MAX_CODE_SECTIONS: usize = 1024;
+const MAX_CONTAINER_SECTIONS: usize = 256;
+const MAX_SECTION_IO: u8 = 0x7f;
+const MAX_STACK_HEIGHT: u16 = 0x03ff;
+/// Outputs of a section that never returns to its caller.
+pub const NON_RETURNING: u8 = 0x80;
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EofError {
+    #[error("missing EOF magic")]
+    MissingMagic,
+    #[error("unsupported EOF version {0}")]
+    UnsupportedVersion(u8),
+    #[error("invalid header: {0}")]
+    InvalidHeader(&'static str),
+    #[error("type of code section {0}: {1}")]
+    InvalidType(usize, &'static str),
+    #[error("container ends before its sections")]
+    Truncated,
+    #[error("data section is {0} bytes shorter than declared")]
+    TruncatedData(usize),
+    #[error("{0} bytes after the data section")]
+    TrailingBytes(usize),
+    #[error("code section {section} at {pc}: {error}")]
+    InvalidCode {
+        section: usize,
+        pc: usize,
+        error: CodeError,
+    },
+    #[error("code section {0} is unreachable")]
+    UnreachableSection(usize),
+    #[error("container section {0} is not referenced")]
+    UnreferencedContainer(usize),
+    #[error("container section {0}: {1}")]
+    InvalidContainer(usize, Box<EofError>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CodeError {
+    #[error("undefined opcode 0x{0:02x}")]
+    UndefinedOpcode(u8),
+    #[error("truncated immediate")]
+    TruncatedImmediate,
+    #[error("jump to {0} is not an instruction")]
+    InvalidJumpTarget(isize),
+    #[error("code section {0} does not exist")]
+    InvalidSection(u16),
+    #[error("container section {0} does not exist")]
+    InvalidContainer(u8),
+    #[error("CALLF to non-returning section {0}")]
+    CallfToNonReturning(u16),
+    #[error("JUMPF to returning section {0} from a non-returning section")]
+    JumpfToReturning(u16),
+    #[error("JUMPF to section {0} which returns more outputs")]
+    JumpfToMoreOutputs(u16),
+    #[error("RETF in a non-returning section")]
+    RetfInNonReturning,
+    #[error("section returns but has no RETF or JUMPF to a returning section")]
+    ReturningWithoutReturn,
+    #[error("data read past the declared data size")]
+    DataOutOfBounds,
+    #[error("unreachable instruction")]
+    Unreachable,
+    #[error("code can run past its end")]
+    MissingTerminator,
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("stack overflow")]
+    StackOverflow,
+    #[error("stack height differs between paths")]
+    StackHeightMismatch,
+    #[error("max stack height is {computed}, {declared} declared")]
+    MaxStackHeightMismatch { declared: u16, computed: usize },
+}
+
+/// Entry of the type section, the signature of a code section.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TypeSection {
+    pub inputs: u8,
+    /// Stack items left for the caller, [`NON_RETURNING`] if the section never returns.
+    pub outputs: u8,
+    pub max_stack_height: u16,
+}
+
+impl TypeSection {
+    pub fn is_returning(&self) -> bool {
+        self.outputs != NON_RETURNING
+    }
+}
+
+/// EOF container borrowing its sections from the code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EofContainer<'a> {
+    pub version: u8,
+    pub types: Vec<TypeSection>,
+    pub code_sections: Vec<&'a [u8]>,
+    /// Nested containers, parsed on demand with [`EofContainer::container`].
+    pub container_sections: Vec<&'a [u8]>,
+    pub data: &'a [u8],
+    /// Data size in the header, larger than `data` in a container deployed with appended data.
+    pub data_size: u16,
+}
+
+/// Whether `code` is an EOF container rather than legacy code, EIP-3541 rejects deploying legacy
+/// code starting with 0xEF.
+pub fn is_eof(code: &[u8]) -> bool {
+    code.starts_with(&EOF_MAGIC)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, EofError> {
+        self.take(1)
+            .map(|b| b[0])
+            .ok_or(EofError::InvalidHeader("truncated"))
+    }
+
+    fn u16(&mut self) -> Result<u16, EofError> {
+        self.take(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or(EofError::InvalidHeader("truncated"))
+    }
+
+    fn u32(&mut self) -> Result<u32, EofError> {
+        self.take(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or(EofError::InvalidHeader("truncated"))
+    }
+
+    fn expect(&mut self, kind: u8, error: &'static str) -> Result<(), EofError> {
+        match self.u8()? {
+            byte if byte == kind => Ok(()),
+            _ => Err(EofError::InvalidHeader(error)),
+        }
+    }
+}
+
+impl<'a> EofContainer<'a> {
+    /// Parse the header and split the sections, the code itself is checked by
+    /// [`EofContainer::validate`].
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, EofError> {
+        let container = Self::parse_partial(bytes)?;
+        match container.data_size as usize - container.data.len() {
+            0 => Ok(container),
+            missing => Err(EofError::TruncatedData(missing)),
+        }
+    }
+
+    /// Like [`EofContainer::parse`], allowing a data section shorter than declared as in
+    /// subcontainers, which get the rest appended on deployment.
+    pub fn parse_partial(bytes: &'a [u8]) -> Result<Self, EofError> {
+        if !is_eof(bytes) {
+            return Err(EofError::MissingMagic);
+        }
+        let mut header = Reader { bytes, pos: 2 };
+        let version = header.u8()?;
+        if version != EOF_VERSION {
+            return Err(EofError::UnsupportedVersion(version));
+        }
+
+        header.expect(KIND_TYPE, "missing type section")?;
+        let types_size = header.u16()? as usize;
+        header.expect(KIND_CODE, "missing code sections")?;
+        let num_code_sections = header.u16()? as usize;
+        if num_code_sections == 0 || num_code_sections > MAX_CODE_SECTIONS {
+            return Err(EofError::InvalidHeader("invalid number of code sections"));
+        }
+        if types_size != num_code_sections * 4 {
+            return Err(EofError::InvalidHeader(
+                "type section size does not match the code sections",
+            ));
+        }
+        let code_sizes = (0..num_code_sections)
+            .map(|_| header.u16().map(usize::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        if code_sizes.contains(&0) {
+            return Err(EofError::InvalidHeader("empty code section"));
+        }
+        let mut container_sizes = vec![];
+        if bytes.get(header.pos) == Some(&KIND_CONTAINER) {
+            header.pos += 1;
+            let num_container_sections = header.u16()? as usize;
+            if num_container_sections == 0 || num_container_sections > MAX_CONTAINER_SECTIONS {
+                return Err(EofError::InvalidHeader(
+                    "invalid number of container sections",
+                ));
+            }
+            container_sizes = (0..num_container_sections)
+                .map(|_| header.u32().map(|size| size as usize))
+                .collect::<Result<Vec<_>, _>>()?;
+            if container_sizes.contains(&0) {
+                return Err(EofError::InvalidHeader("empty container section"));
+            }
+        }
+        header.expect(KIND_DATA, "missing data section")?;
+        let data_size = header.u16()?;
+        header.expect(TERMINATOR, "missing terminator")?;
+
+        let mut body = header;
+        let types = (0..num_code_sections)
+            .map(|_| {
+                let entry = body.take(4).ok_or(EofError::Truncated)?;
+                Ok(TypeSection {
+                    inputs: entry[0],
+                    outputs: entry[1],
+                    max_stack_height: u16::from_be_bytes([entry[2], entry[3]]),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (i, ty) in types.iter().enumerate() {
+            if ty.inputs > MAX_SECTION_IO {
+                return Err(EofError::InvalidType(i, "too many inputs"));
+            }
+            if ty.outputs > MAX_SECTION_IO && ty.is_returning() {
+                return Err(EofError::InvalidType(i, "too many outputs"));
+            }
+            if ty.max_stack_height > MAX_STACK_HEIGHT {
+                return Err(EofError::InvalidType(i, "max stack height above the limit"));
+            }
+        }
+        if types[0].inputs != 0 || types[0].is_returning() {
+            return Err(EofError::InvalidType(
+                0,
+                "first section must take no inputs and not return",
+            ));
+        }
+        let code_sections = code_sizes
+            .into_iter()
+            .map(|size| body.take(size).ok_or(EofError::Truncated))
+            .collect::<Result<Vec<_>, _>>()?;
+        let container_sections = container_sizes
+            .into_iter()
+            .map(|size| body.take(size).ok_or(EofError::Truncated))
+            .collect::<Result<Vec<_>, _>>()?;
+        let data = &bytes[body.pos..];
+        if data.len() > data_size as usize {
+            return Err(EofError::TrailingBytes(data.len() - data_size as usize));
+        }
+
+        Ok(Self {
+            version,
+            types,
+            code_sections,
+            container_sections,
+            data,
+            data_size,
+        })
+    }
+
+    /// Parse the `index`th subcontainer, `None` if there is no such section.
+    pub fn container(&self, index: usize) -> Option<Result<EofContainer<'a>, EofError>> {
+        let bytes = self.container_sections.get(index)?;
+        Some(EofContainer::parse_partial(bytes))
+    }
+
+    /// Instructions of the `section`th code section as `(pc, opcode, immediate)`, immediates are
+    /// cut short at the end of unvalidated code. `None` if there is no such section.
+    pub fn instructions(&self, section: usize) -> Option<EofInstructions<'a>> {
+        let code = self.code_sections.get(section)?;
+        Some(EofInstructions { code, pc: 0 })
+    }
+
+    /// Instructions of all code sections in order, see [`EofContainer::instructions`].
+    pub fn all_instructions(&self) -> impl Iterator<Item = (usize, OpcodeId, &'a [u8])> + '_ {
+        self.code_sections
+            .iter()
+            .flat_map(|code| EofInstructions { code, pc: 0 })
+    }
+
+    /// Validate all code sections and subcontainers.
+    pub fn validate(&self) -> Result<(), EofError> {
+        let mut referenced = vec![false; self.container_sections.len()];
+        let called = (0..self.code_sections.len())
+            .map(|section| {
+                let (sections, containers) = self
+                    .validate_code(section)
+                    .map_err(|(pc, error)| EofError::InvalidCode { section, pc, error })?;
+                for container in containers {
+                    referenced[container] = true;
+                }
+                Ok(sections)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut reachable = vec![false; self.code_sections.len()];
+        let mut queue = vec![0];
+        while let Some(section) = queue.pop() {
+            if !std::mem::replace(&mut reachable[section], true) {
+                queue.extend(called[section].iter().copied());
+            }
+        }
+        if let Some(section) = reachable.iter().position(|reachable| !reachable) {
+            return Err(EofError::UnreachableSection(section));
+        }
+        if let Some(container) = referenced.iter().position(|referenced| !referenced) {
+            return Err(EofError::UnreferencedContainer(container));
+        }
+
+        for (index, bytes) in self.container_sections.iter().enumerate() {
+            EofContainer::parse_partial(bytes)
+                .and_then(|container| container.validate())
+                .map_err(|error| EofError::InvalidContainer(index, Box::new(error)))?;
+        }
+        Ok(())
+    }
+
+    /// Validate one code section, returning the sections it calls and containers it references,
+    /// or the offending pc.
+    fn validate_code(
+        &self,
+        section: usize,
+    ) -> Result<(BTreeSet<usize>, BTreeSet<usize>), (usize, CodeError)> {
+        let code = self.code_sections[section];
+        let ty = self.types[section];
+        let mut sections = BTreeSet::new();
+        let mut containers = BTreeSet::new();
+        let mut is_instruction = vec![false; code.len()];
+        let mut jumps = vec![];
+        let mut returns = false;
+
+        // instructions, immediates and references (EIP-3670, EIP-4200, EIP-4750)
+        let mut pc = 0;
+        while pc < code.len() {
+            let op = OpcodeId::from_eof(code[pc]);
+            if op.is_other_invalid() {
+                return Err((pc, CodeError::UndefinedOpcode(code[pc])));
+            }
+            let size = immediate_size(code, pc).ok_or((pc, CodeError::TruncatedImmediate))?;
+            let immediate = code
+                .get(pc + 1..pc + 1 + size)
+                .ok_or((pc, CodeError::TruncatedImmediate))?;
+            is_instruction[pc] = true;
+            let next = pc + 1 + size;
+            match op {
+                OpcodeId::RJUMP | OpcodeId::RJUMPI | OpcodeId::RJUMPV => {
+                    jumps.extend(jump_targets(op, immediate, next).map(|target| (pc, target)));
+                }
+                OpcodeId::CALLF | OpcodeId::JUMPF => {
+                    let index = u16::from_be_bytes([immediate[0], immediate[1]]);
+                    let target = self
+                        .types
+                        .get(index as usize)
+                        .ok_or((pc, CodeError::InvalidSection(index)))?;
+                    if op == OpcodeId::CALLF && !target.is_returning() {
+                        return Err((pc, CodeError::CallfToNonReturning(index)));
+                    }
+                    if op == OpcodeId::JUMPF && target.is_returning() {
+                        if !ty.is_returning() {
+                            return Err((pc, CodeError::JumpfToReturning(index)));
+                        }
+                        if target.outputs > ty.outputs {
+                            return Err((pc, CodeError::JumpfToMoreOutputs(index)));
+                        }
+                        returns = true;
+                    }
+                    sections.insert(index as usize);
+                }
+                OpcodeId::RETF => {
+                    if !ty.is_returning() {
+                        return Err((pc, CodeError::RetfInNonReturning));
+                    }
+                    returns = true;
+                }
+                OpcodeId::DATALOADN => {
+                    let offset = u16::from_be_bytes([immediate[0], immediate[1]]) as usize;
+                    if offset + 32 > self.data_size as usize {
+                        return Err((pc, CodeError::DataOutOfBounds));
+                    }
+                }
+                OpcodeId::EOFCREATE | OpcodeId::RETURNCONTRACT => {
+                    let index = immediate[0];
+                    if index as usize >= self.container_sections.len() {
+                        return Err((pc, CodeError::InvalidContainer(index)));
+                    }
+                    containers.insert(index as usize);
+                }
+                _ => {}
+            }
+            pc = next;
+        }
+        for (pc, target) in jumps {
+            if !usize::try_from(target).is_ok_and(|t| is_instruction.get(t) == Some(&true)) {
+                return Err((pc, CodeError::InvalidJumpTarget(target)));
+            }
+        }
+        if ty.is_returning() && !returns {
+            return Err((0, CodeError::ReturningWithoutReturn));
+        }
+
+        // stack heights (EIP-5450), instructions are visited in order as only backward jumps
+        // reach instructions already visited, which must agree on the height
+        let mut heights: Vec<Option<(usize, usize)>> = vec![None; code.len()];
+        heights[0] = Some((ty.inputs as usize, ty.inputs as usize));
+        let mut max_height = ty.inputs as usize;
+        for (pc, op, immediate) in (EofInstructions { code, pc: 0 }) {
+            let (min, max) = heights[pc].ok_or((pc, CodeError::Unreachable))?;
+            let (inputs, outputs) = match op {
+                OpcodeId::CALLF | OpcodeId::JUMPF => {
+                    let index = u16::from_be_bytes([immediate[0], immediate[1]]) as usize;
+                    let target = self.types[index];
+                    // the callee's own validation checks its max stack height covers its inputs
+                    let growth =
+                        (target.max_stack_height as usize).saturating_sub(target.inputs as usize);
+                    if max + growth > STACK_LIMIT {
+                        return Err((pc, CodeError::StackOverflow));
+                    }
+                    if op == OpcodeId::JUMPF {
+                        if target.is_returning() {
+                            let height = (ty.outputs as usize + target.inputs as usize)
+                                .checked_sub(target.outputs as usize);
+                            if height != Some(min) || height != Some(max) {
+                                return Err((pc, CodeError::StackHeightMismatch));
+                            }
+                        } else if min < target.inputs as usize {
+                            return Err((pc, CodeError::StackUnderflow));
+                        }
+                        continue;
+                    }
+                    (target.inputs as usize, target.outputs as usize)
+                }
+                OpcodeId::RETF => {
+                    if min != ty.outputs as usize || max != ty.outputs as usize {
+                        return Err((pc, CodeError::StackHeightMismatch));
+                    }
+                    continue;
+                }
+                _ => eof_stack_effect(op, immediate),
+            };
+            if min < inputs {
+                return Err((pc, CodeError::StackUnderflow));
+            }
+            let after = (min - inputs + outputs, max - inputs + outputs);
+            if after.1 > STACK_LIMIT {
+                return Err((pc, CodeError::StackOverflow));
+            }
+            max_height = max_height.max(after.1);
+
+            let next = pc + 1 + immediate.len();
+            let mut successors = jump_targets(op, immediate, next).collect::<Vec<_>>();
+            if !is_terminating(op) && op != OpcodeId::RJUMP {
+                successors.push(next as isize);
+            }
+            for successor in successors {
+                // jump targets are valid instructions at this point
+                let successor = successor as usize;
+                if successor >= code.len() {
+                    return Err((pc, CodeError::MissingTerminator));
+                }
+                match &mut heights[successor] {
+                    height if successor <= pc => {
+                        if *height != Some(after) {
+                            return Err((pc, CodeError::StackHeightMismatch));
+                        }
+                    }
+                    Some((min, max)) => {
+                        *min = (*min).min(after.0);
+                        *max = (*max).max(after.1);
+                    }
+                    height => *height = Some(after),
+                }
+            }
+        }
+        if max_height != ty.max_stack_height as usize {
+            return Err((
+                0,
+                CodeError::MaxStackHeightMismatch {
+                    declared: ty.max_stack_height,
+                    computed: max_height,
+                },
+            ));
+        }
+        Ok((sections, containers))
+    }
+}
+
+/// Instructions of an EOF code section, see [`EofContainer::instructions`].
+#[derive(Clone, Debug)]
+pub struct EofInstructions<'a> {
+    code: &'a [u8],
+    pc: usize,
+}
+
+impl<'a> Iterator for EofInstructions<'a> {
+    type Item = (usize, OpcodeId, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let byte = *self.code.get(self.pc)?;
+        let start = self.pc;
+        let size = immediate_size(self.code, start).unwrap_or(0);
+        let end = (start + 1 + size).min(self.code.len());
+        self.pc = end;
+        Some((start, OpcodeId::from_eof(byte), &self.code[start + 1..end]))
+    }
+}
+
+/// Immediate size of the instruction at `pc`, `None` if the `RJUMPV` table size is missing.
+fn immediate_size(code: &[u8], pc: usize) -> Option<usize> {
+    Some(match OpcodeId::from_eof(code[pc]) {
+        op if op.is_push_with_data() => op.postfix().unwrap() as usize,
+        OpcodeId::RJUMP
+        | OpcodeId::RJUMPI
+        | OpcodeId::CALLF
+        | OpcodeId::JUMPF
+        | OpcodeId::DATALOADN => 2,
+        OpcodeId::DUPN
+        | OpcodeId::SWAPN
+        | OpcodeId::EXCHANGE
+        | OpcodeId::EOFCREATE
+        | OpcodeId::RETURNCONTRACT => 1,
+        OpcodeId::RJUMPV => 1 + (*code.get(pc + 1)? as usize + 1) * 2,
+        _ => 0,
+    })
+}
+
+/// Targets of a relative jump whose next instruction is at `next`.
+fn jump_targets(op: OpcodeId, immediate: &[u8], next: usize) -> impl Iterator<Item = isize> + '_ {
+    let offsets = match op {
+        OpcodeId::RJUMP | OpcodeId::RJUMPI => immediate,
+        OpcodeId::RJUMPV => &immediate[1..],
+        _ => &[],
+    };
+    offsets
+        .chunks_exact(2)
+        .map(move |offset| next as isize + i16::from_be_bytes([offset[0], offset[1]]) as isize)
+}
+
+/// Stack effect including the opcodes whose effect depends on the immediate.
+fn eof_stack_effect(op: OpcodeId, immediate: &[u8]) -> (usize, usize) {
+    match op {
+        OpcodeId::DUPN => {
+            let n = immediate[0] as usize + 1;
+            (n, n + 1)
+        }
+        OpcodeId::SWAPN => {
+            let n = immediate[0] as usize + 2;
+            (n, n)
+        }
+        OpcodeId::EXCHANGE => {
+            let n = (immediate[0] >> 4) as usize + (immediate[0] & 0x0f) as usize + 3;
+            (n, n)
+        }
+        _ => op.stack_effect(),
+    }
+}
+
+/// Whether execution never continues after `op`.
+fn is_terminating(op: OpcodeId) -> bool {
+    matches!(
+        op,
+        OpcodeId::STOP
+            | OpcodeId::RETURN
+            | OpcodeId::REVERT
+            | OpcodeId::INVALID(_)
+            | OpcodeId::RETF
+            | OpcodeId::JUMPF
+            | OpcodeId::RETURNCONTRACT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Container with the code sections `(inputs, outputs, max_stack_height, code)`.
+    fn container(sections: &[(u8, u8, u16, &[u8])], containers: &[&[u8]], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0xef, 0x00, EOF_VERSION, KIND_TYPE];
+        bytes.extend((sections.len() as u16 * 4).to_be_bytes());
+        bytes.push(KIND_CODE);
+        bytes.extend((sections.len() as u16).to_be_bytes());
+        for (.., code) in sections {
+            bytes.extend((code.len() as u16).to_be_bytes());
+        }
+        if !containers.is_empty() {
+            bytes.push(KIND_CONTAINER);
+            bytes.extend((containers.len() as u16).to_be_bytes());
+            for container in containers {
+                bytes.extend((container.len() as u32).to_be_bytes());
+            }
+        }
+        bytes.push(KIND_DATA);
+        bytes.extend((data.len() as u16).to_be_bytes());
+        bytes.push(TERMINATOR);
+        for (inputs, outputs, max_stack_height, _) in sections {
+            bytes.extend([*inputs, *outputs]);
+            bytes.extend(max_stack_height.to_be_bytes());
+        }
+        for (.., code) in sections {
+            bytes.extend_from_slice(code);
+        }
+        for container in containers {
+            bytes.extend_from_slice(container);
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn validate(bytes: &[u8]) -> Result<(), EofError> {
+        EofContainer::parse(bytes)?.validate()
+    }
+
+    fn code_error(section: usize, pc: usize, error: CodeError) -> Result<(), EofError> {
+        Err(EofError::InvalidCode { section, pc, error })
+    }
+
+    const STOP: &[u8] = &[0x00];
+
+    #[test]
+    fn header_errors() {
+        assert_eq!(
+            EofContainer::parse(&[0x60, 0x00]),
+            Err(EofError::MissingMagic)
+        );
+        assert_eq!(
+            EofContainer::parse(&[0xef, 0x00, 0x02]),
+            Err(EofError::UnsupportedVersion(2))
+        );
+
+        let valid = container(&[(0, NON_RETURNING, 0, STOP)], &[], &[0xaa]);
+        assert_eq!(validate(&valid), Ok(()));
+        // type section size of 8 for one code section
+        let mut bytes = valid.clone();
+        bytes[5] = 0x08;
+        assert_eq!(
+            EofContainer::parse(&bytes),
+            Err(EofError::InvalidHeader(
+                "type section size does not match the code sections"
+            ))
+        );
+        // no code sections
+        let mut bytes = valid.clone();
+        bytes[8] = 0x00;
+        assert_eq!(
+            EofContainer::parse(&bytes),
+            Err(EofError::InvalidHeader("invalid number of code sections"))
+        );
+        let mut bytes = valid.clone();
+        bytes[14] = 0x01;
+        assert_eq!(
+            EofContainer::parse(&bytes),
+            Err(EofError::InvalidHeader("missing terminator"))
+        );
+        assert_eq!(
+            EofContainer::parse(&valid[..10]),
+            Err(EofError::InvalidHeader("truncated"))
+        );
+        assert_eq!(
+            EofContainer::parse(&valid[..valid.len() - 1]),
+            Err(EofError::TruncatedData(1))
+        );
+        assert!(EofContainer::parse_partial(&valid[..valid.len() - 1]).is_ok());
+        let mut bytes = valid.clone();
+        bytes.push(0xbb);
+        assert_eq!(EofContainer::parse(&bytes), Err(EofError::TrailingBytes(1)));
+        // the first section returns
+        let bytes = container(&[(0, 0, 0, &[0xe4])], &[], &[]);
+        assert!(matches!(
+            EofContainer::parse(&bytes),
+            Err(EofError::InvalidType(0, _))
+        ));
+    }
+
+    #[test]
+    fn rjump_into_immediate() {
+        // RJUMP +1, PUSH1 0x00, STOP
+        let code = [0xe0, 0x00, 0x01, 0x60, 0x00, 0x00];
+        let bytes = container(&[(0, NON_RETURNING, 1, &code)], &[], &[]);
+        assert_eq!(
+            validate(&bytes),
+            code_error(0, 0, CodeError::InvalidJumpTarget(4))
+        );
+    }
+
+    #[test]
+    fn callf_to_non_returning() {
+        // CALLF 1, STOP
+        let code = [0xe3, 0x00, 0x01, 0x00];
+        let bytes = container(
+            &[(0, NON_RETURNING, 0, &code), (0, NON_RETURNING, 0, STOP)],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            validate(&bytes),
+            code_error(0, 0, CodeError::CallfToNonReturning(1))
+        );
+    }
+
+    #[test]
+    fn jumpf_stack_heights() {
+        // CALLF 1, POP, STOP
+        let main = (0, NON_RETURNING, 1, &[0xe3, 0x00, 0x01, 0x50, 0x00][..]);
+        // RETF with its single input as output
+        let identity = (1, 1, 1, &[0xe4][..]);
+
+        // PUSH1 1, JUMPF 2
+        let tail_call = (0, 1, 1, &[0x60, 0x01, 0xe5, 0x00, 0x02][..]);
+        let bytes = container(&[main, tail_call, identity], &[], &[]);
+        assert_eq!(validate(&bytes), Ok(()));
+
+        // PUSH1 1, PUSH1 1, JUMPF 2 leaves an extra item for the caller
+        let tail_call = (0, 1, 2, &[0x60, 0x01, 0x60, 0x01, 0xe5, 0x00, 0x02][..]);
+        let bytes = container(&[main, tail_call, identity], &[], &[]);
+        assert_eq!(
+            validate(&bytes),
+            code_error(1, 4, CodeError::StackHeightMismatch)
+        );
+
+        // JUMPF 2 to a section returning more than this one, with nothing for its input
+        let bytes = container(
+            &[
+                (0, NON_RETURNING, 0, &[0xe3, 0x00, 0x01, 0x00]),
+                (0, 0, 0, &[0xe5, 0x00, 0x02]),
+                identity,
+            ],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            validate(&bytes),
+            code_error(1, 0, CodeError::JumpfToMoreOutputs(2))
+        );
+
+        // JUMPF 1 to a returning section from a non-returning one
+        let bytes = container(
+            &[
+                (0, NON_RETURNING, 0, &[0xe5, 0x00, 0x01]),
+                (0, 0, 0, &[0xe4]),
+            ],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            validate(&bytes),
+            code_error(0, 0, CodeError::JumpfToReturning(1))
+        );
+
+        // JUMPF 1 to a non-returning section taking an input that is not there
+        let bytes = container(
+            &[
+                (0, NON_RETURNING, 0, &[0xe5, 0x00, 0x01]),
+                (1, NON_RETURNING, 1, &[0x50, 0x00]),
+            ],
+            &[],
+            &[],
+        );
+        assert_eq!(
+            validate(&bytes),
+            code_error(0, 0, CodeError::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn backward_jump_height_mismatch() {
+        // PUSH1 1, RJUMP -5 back to the start with one more item
+        let code = [0x60, 0x01, 0xe0, 0xff, 0xfb];
+        let bytes = container(&[(0, NON_RETURNING, 1, &code)], &[], &[]);
+        assert_eq!(
+            validate(&bytes),
+            code_error(0, 2, CodeError::StackHeightMismatch)
+        );
+    }
+
+    #[test]
+    fn unreferenced_container() {
+        let sub = container(&[(0, NON_RETURNING, 0, STOP)], &[], &[]);
+        let bytes = container(&[(0, NON_RETURNING, 0, STOP)], &[&sub], &[]);
+        assert_eq!(validate(&bytes), Err(EofError::UnreferencedContainer(0)));
+
+        // PUSH0 x4, EOFCREATE 0, STOP
+        let code = [0x5f, 0x5f, 0x5f, 0x5f, 0xec, 0x00, 0x00];
+        let bytes = container(&[(0, NON_RETURNING, 4, &code)], &[&sub], &[]);
+        assert_eq!(validate(&bytes), Ok(()));
+    }
+
+    #[test]
+    fn max_stack_height_mismatch() {
+        // PUSH1 1, POP, STOP
+        let code = [0x60, 0x01, 0x50, 0x00];
+        let bytes = container(&[(0, NON_RETURNING, 2, &code)], &[], &[]);
+        assert_eq!(
+            validate(&bytes),
+            code_error(
+                0,
+                0,
+                CodeError::MaxStackHeightMismatch {
+                    declared: 2,
+                    computed: 1
+                }
+            )
+        );
+    }
+}
@@ -1,7 +1,11 @@
 use core::fmt::Debug;
+use std::collections::HashMap;
 use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 /// Opcode enum. One-to-one corresponding to an `u8` value.
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum OpcodeId {
     /// `STOP`
@@ -77,6 +81,8 @@ pub enum OpcodeId {
     MSTORE,
     /// `MSTORE8`
     MSTORE8,
+    /// `MCOPY`
+    MCOPY,
     /// `JUMP`
     JUMP,
     /// `JUMPI`
@@ -275,10 +281,18 @@ pub enum OpcodeId {
     SELFBALANCE,
     /// `BASEFEE`
     BASEFEE,
+    /// `BLOBHASH`
+    BLOBHASH,
+    /// `BLOBBASEFEE`
+    BLOBBASEFEE,
     /// `SLOAD`
     SLOAD,
     /// `SSTORE`
     SSTORE,
+    /// `TLOAD`
+    TLOAD,
+    /// `TSTORE`
+    TSTORE,
     /// `GAS`
     GAS,
 
@@ -308,6 +322,46 @@ pub enum OpcodeId {
     STATICCALL,
     /// `SELFDESTRUCT`
     SELFDESTRUCT,
+
+    // EOF opcodes, only defined in EOF code
+    /// `DATALOAD`
+    DATALOAD,
+    /// `DATALOADN`
+    DATALOADN,
+    /// `DATASIZE`
+    DATASIZE,
+    /// `DATACOPY`
+    DATACOPY,
+    /// `RJUMP`
+    RJUMP,
+    /// `RJUMPI`
+    RJUMPI,
+    /// `RJUMPV`
+    RJUMPV,
+    /// `CALLF`
+    CALLF,
+    /// `RETF`
+    RETF,
+    /// `JUMPF`
+    JUMPF,
+    /// `DUPN`
+    DUPN,
+    /// `SWAPN`
+    SWAPN,
+    /// `EXCHANGE`
+    EXCHANGE,
+    /// `EOFCREATE`
+    EOFCREATE,
+    /// `RETURNCONTRACT`
+    RETURNCONTRACT,
+    /// `RETURNDATALOAD`
+    RETURNDATALOAD,
+    /// `EXTCALL`
+    EXTCALL,
+    /// `EXTDELEGATECALL`
+    EXTDELEGATECALL,
+    /// `EXTSTATICCALL`
+    EXTSTATICCALL,
 }
 
 impl OpcodeId {
@@ -376,6 +430,7 @@ impl OpcodeId {
             OpcodeId::MLOAD => 0x51u8,
             OpcodeId::MSTORE => 0x52u8,
             OpcodeId::MSTORE8 => 0x53u8,
+            OpcodeId::MCOPY => 0x5eu8,
             OpcodeId::JUMP => 0x56u8,
             OpcodeId::JUMPI => 0x57u8,
             OpcodeId::PC => 0x58u8,
@@ -470,8 +525,12 @@ impl OpcodeId {
             OpcodeId::CHAINID => 0x46u8,
             OpcodeId::SELFBALANCE => 0x47u8,
             OpcodeId::BASEFEE => 0x48u8,
+            OpcodeId::BLOBHASH => 0x49u8,
+            OpcodeId::BLOBBASEFEE => 0x4au8,
             OpcodeId::SLOAD => 0x54u8,
             OpcodeId::SSTORE => 0x55u8,
+            OpcodeId::TLOAD => 0x5cu8,
+            OpcodeId::TSTORE => 0x5du8,
             OpcodeId::GAS => 0x5au8,
             OpcodeId::LOG0 => 0xa0u8,
             OpcodeId::LOG1 => 0xa1u8,
@@ -485,6 +544,25 @@ impl OpcodeId {
             OpcodeId::DELEGATECALL => 0xf4u8,
             OpcodeId::STATICCALL => 0xfau8,
             OpcodeId::SELFDESTRUCT => 0xffu8,
+            OpcodeId::DATALOAD => 0xd0u8,
+            OpcodeId::DATALOADN => 0xd1u8,
+            OpcodeId::DATASIZE => 0xd2u8,
+            OpcodeId::DATACOPY => 0xd3u8,
+            OpcodeId::RJUMP => 0xe0u8,
+            OpcodeId::RJUMPI => 0xe1u8,
+            OpcodeId::RJUMPV => 0xe2u8,
+            OpcodeId::CALLF => 0xe3u8,
+            OpcodeId::RETF => 0xe4u8,
+            OpcodeId::JUMPF => 0xe5u8,
+            OpcodeId::DUPN => 0xe6u8,
+            OpcodeId::SWAPN => 0xe7u8,
+            OpcodeId::EXCHANGE => 0xe8u8,
+            OpcodeId::EOFCREATE => 0xecu8,
+            OpcodeId::RETURNCONTRACT => 0xeeu8,
+            OpcodeId::RETURNDATALOAD => 0xf7u8,
+            OpcodeId::EXTCALL => 0xf8u8,
+            OpcodeId::EXTDELEGATECALL => 0xf9u8,
+            OpcodeId::EXTSTATICCALL => 0xfbu8,
         }
     }
 
@@ -509,6 +587,110 @@ impl OpcodeId {
             _ => false,
         }
     }
+
+    /// Returns `true` if the `OpcodeId` is only defined in EOF code.
+    pub fn is_eof_only(&self) -> bool {
+        matches!(
+            self,
+            OpcodeId::DATALOAD
+                | OpcodeId::DATALOADN
+                | OpcodeId::DATASIZE
+                | OpcodeId::DATACOPY
+                | OpcodeId::RJUMP
+                | OpcodeId::RJUMPI
+                | OpcodeId::RJUMPV
+                | OpcodeId::CALLF
+                | OpcodeId::RETF
+                | OpcodeId::JUMPF
+                | OpcodeId::DUPN
+                | OpcodeId::SWAPN
+                | OpcodeId::EXCHANGE
+                | OpcodeId::EOFCREATE
+                | OpcodeId::RETURNCONTRACT
+                | OpcodeId::RETURNDATALOAD
+                | OpcodeId::EXTCALL
+                | OpcodeId::EXTDELEGATECALL
+                | OpcodeId::EXTSTATICCALL
+        )
+    }
+
+    /// Opcode of `value` in EOF code, where the EOF opcodes are defined and the legacy ones
+    /// observing code, gas or jumping dynamically are not.
+    pub fn from_eof(value: u8) -> Self {
+        match value {
+            0x5fu8 => OpcodeId::PUSH0,
+            0xd0u8 => OpcodeId::DATALOAD,
+            0xd1u8 => OpcodeId::DATALOADN,
+            0xd2u8 => OpcodeId::DATASIZE,
+            0xd3u8 => OpcodeId::DATACOPY,
+            0xe0u8 => OpcodeId::RJUMP,
+            0xe1u8 => OpcodeId::RJUMPI,
+            0xe2u8 => OpcodeId::RJUMPV,
+            0xe3u8 => OpcodeId::CALLF,
+            0xe4u8 => OpcodeId::RETF,
+            0xe5u8 => OpcodeId::JUMPF,
+            0xe6u8 => OpcodeId::DUPN,
+            0xe7u8 => OpcodeId::SWAPN,
+            0xe8u8 => OpcodeId::EXCHANGE,
+            0xecu8 => OpcodeId::EOFCREATE,
+            0xeeu8 => OpcodeId::RETURNCONTRACT,
+            0xf7u8 => OpcodeId::RETURNDATALOAD,
+            0xf8u8 => OpcodeId::EXTCALL,
+            0xf9u8 => OpcodeId::EXTDELEGATECALL,
+            0xfbu8 => OpcodeId::EXTSTATICCALL,
+            // CODESIZE, CODECOPY, EXTCODE*, JUMP, JUMPI, PC, GAS, CREATE*, CALL*, SELFDESTRUCT
+            0x38u8 | 0x39u8 | 0x3bu8 | 0x3cu8 | 0x3fu8 | 0x56u8 | 0x57u8 | 0x58u8 | 0x5au8
+            | 0xf0u8 | 0xf1u8 | 0xf2u8 | 0xf4u8 | 0xf5u8 | 0xfau8 | 0xffu8 => {
+                OpcodeId::INVALID(value)
+            }
+            _ => OpcodeId::from(value),
+        }
+    }
+
+    /// Number of stack items taken and pushed back.
+    ///
+    /// `DUPN`, `SWAPN` and `EXCHANGE` depend on their immediate, `CALLF` and `JUMPF` on the
+    /// type of the called section, they are reported as taking and pushing nothing.
+    pub fn stack_effect(&self) -> (usize, usize) {
+        use OpcodeId::*;
+        // no wildcard, so a new opcode does not compile until its effect is added
+        match self {
+            STOP | JUMPDEST | INVALID(_) | RJUMP | CALLF | RETF | JUMPF | DUPN | SWAPN
+            | EXCHANGE => (0, 0),
+            ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE
+            | RETURNDATASIZE | COINBASE | TIMESTAMP | NUMBER | DIFFICULTY | GASLIMIT | CHAINID
+            | SELFBALANCE | BASEFEE | BLOBBASEFEE | PC | MSIZE | GAS | DATALOADN | DATASIZE => {
+                (0, 1)
+            }
+            POP | JUMP | SELFDESTRUCT | RJUMPI | RJUMPV => (1, 0),
+            ISZERO | NOT | BALANCE | CALLDATALOAD | EXTCODESIZE | EXTCODEHASH | BLOCKHASH
+            | BLOBHASH | MLOAD | SLOAD | TLOAD | DATALOAD | RETURNDATALOAD => (1, 1),
+            MSTORE | MSTORE8 | SSTORE | TSTORE | JUMPI | RETURN | REVERT | RETURNCONTRACT => (2, 0),
+            ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | EXP | SIGNEXTEND | LT | GT | SLT | SGT
+            | EQ | AND | OR | XOR | BYTE | SHL | SHR | SAR | SHA3 => (2, 1),
+            CALLDATACOPY | CODECOPY | RETURNDATACOPY | MCOPY | DATACOPY => (3, 0),
+            ADDMOD | MULMOD | CREATE | EXTDELEGATECALL | EXTSTATICCALL => (3, 1),
+            EXTCODECOPY => (4, 0),
+            CREATE2 | EOFCREATE | EXTCALL => (4, 1),
+            DELEGATECALL | STATICCALL => (6, 1),
+            CALL | CALLCODE => (7, 1),
+            PUSH0 | PUSH1 | PUSH2 | PUSH3 | PUSH4 | PUSH5 | PUSH6 | PUSH7 | PUSH8 | PUSH9
+            | PUSH10 | PUSH11 | PUSH12 | PUSH13 | PUSH14 | PUSH15 | PUSH16 | PUSH17 | PUSH18
+            | PUSH19 | PUSH20 | PUSH21 | PUSH22 | PUSH23 | PUSH24 | PUSH25 | PUSH26 | PUSH27
+            | PUSH28 | PUSH29 | PUSH30 | PUSH31 | PUSH32 => (0, 1),
+            op @ (DUP1 | DUP2 | DUP3 | DUP4 | DUP5 | DUP6 | DUP7 | DUP8 | DUP9 | DUP10 | DUP11
+            | DUP12 | DUP13 | DUP14 | DUP15 | DUP16) => {
+                let n = op.postfix().unwrap() as usize;
+                (n, n + 1)
+            }
+            op @ (SWAP1 | SWAP2 | SWAP3 | SWAP4 | SWAP5 | SWAP6 | SWAP7 | SWAP8 | SWAP9
+            | SWAP10 | SWAP11 | SWAP12 | SWAP13 | SWAP14 | SWAP15 | SWAP16) => {
+                let n = op.postfix().unwrap() as usize + 1;
+                (n, n)
+            }
+            op @ (LOG0 | LOG1 | LOG2 | LOG3 | LOG4) => (op.postfix().unwrap() as usize + 2, 0),
+        }
+    }
 }
 
 impl From<u8> for OpcodeId {
@@ -549,12 +731,12 @@ impl From<u8> for OpcodeId {
             0x51u8 => OpcodeId::MLOAD,
             0x52u8 => OpcodeId::MSTORE,
             0x53u8 => OpcodeId::MSTORE8,
+            0x5eu8 => OpcodeId::MCOPY,
             0x56u8 => OpcodeId::JUMP,
             0x57u8 => OpcodeId::JUMPI,
             0x58u8 => OpcodeId::PC,
             0x59u8 => OpcodeId::MSIZE,
             0x5bu8 => OpcodeId::JUMPDEST,
-            #[cfg(feature = "shanghai")]
             0x5fu8 => OpcodeId::PUSH0,
             0x60u8 => OpcodeId::PUSH1,
             0x61u8 => OpcodeId::PUSH2,
@@ -644,8 +826,12 @@ impl From<u8> for OpcodeId {
             0x46u8 => OpcodeId::CHAINID,
             0x47u8 => OpcodeId::SELFBALANCE,
             0x48u8 => OpcodeId::BASEFEE,
+            0x49u8 => OpcodeId::BLOBHASH,
+            0x4au8 => OpcodeId::BLOBBASEFEE,
             0x54u8 => OpcodeId::SLOAD,
             0x55u8 => OpcodeId::SSTORE,
+            0x5cu8 => OpcodeId::TLOAD,
+            0x5du8 => OpcodeId::TSTORE,
             0x5au8 => OpcodeId::GAS,
             0xa0u8 => OpcodeId::LOG0,
             0xa1u8 => OpcodeId::LOG1,
@@ -669,3 +855,35 @@ impl fmt::Display for OpcodeId {
         write!(f, "{self:?}")
     }
 }
+
+impl FromStr for OpcodeId {
+    type Err = String;
+
+    /// Parse an opcode from its name, also accepting the newer names geth uses and its
+    /// `opcode 0xef not defined` for undefined ones.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(byte) = s
+            .strip_prefix("opcode 0x")
+            .and_then(|s| s.strip_suffix(" not defined"))
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        {
+            return Ok(OpcodeId::INVALID(byte));
+        }
+        static NAMES: OnceLock<HashMap<String, OpcodeId>> = OnceLock::new();
+        let names = NAMES.get_or_init(|| {
+            let mut names = (0..=u8::MAX)
+                .flat_map(|b| [OpcodeId::from(b), OpcodeId::from_eof(b)])
+                .filter(|op| !matches!(op, OpcodeId::INVALID(_)))
+                .map(|op| (op.to_string(), op))
+                .collect::<HashMap<_, _>>();
+            names.insert("KECCAK256".to_string(), OpcodeId::SHA3);
+            names.insert("PREVRANDAO".to_string(), OpcodeId::DIFFICULTY);
+            names.insert("INVALID".to_string(), OpcodeId::INVALID(0xfe));
+            names
+        });
+        names
+            .get(s)
+            .copied()
+            .ok_or_else(|| format!("unknown opcode {s}"))
+    }
+}
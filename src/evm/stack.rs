@@ -0,0 +1,406 @@
+//! Stack height analysis of legacy code over basic blocks
+
+use crate::evm::opcode::OpcodeId;
+use crate::evm::view::BytecodeView;
+use crate::evm::view::Instructions;
+use std::collections::{HashMap, HashSet};
+
+pub const STACK_LIMIT: usize = 1024;
+
+/// Straight-line code between jump targets and jumps, heights relative to the block entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Offset of the first instruction.
+    pub start: usize,
+    /// Offset after the last instruction.
+    pub end: usize,
+    /// Lowest height reached, minus the stack items the block needs on entry.
+    pub min_height: isize,
+    /// Highest height reached.
+    pub max_height: isize,
+    /// Height on exit.
+    pub delta: isize,
+    /// Blocks continued at by falling through or static jumps.
+    pub successors: Vec<usize>,
+    /// Whether the block ends with a jump to a computed target, which may be any `JUMPDEST`.
+    pub dynamic_jump: bool,
+}
+
+/// Distinct stacks a block is run with before [`StackAnalysis`] stops following it, recursion
+/// would otherwise be followed until the stack overflows.
+const MAX_BLOCK_STATES: usize = 64;
+
+/// Result of [`StackAnalysis::new`].
+///
+/// Paths are followed from the start of the code with the exact stack height, tracking pushed
+/// `JUMPDEST` offsets so the return jumps of internal functions resolve to the pushed return
+/// address. Both branches of a `JUMPI` are followed, so a path may not be feasible.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackAnalysis {
+    pub blocks: Vec<BasicBlock>,
+    /// Lowest and highest height on entry of each block, `None` if no path reached it.
+    pub entry_heights: Vec<Option<(usize, usize)>>,
+    /// Highest height on any path.
+    pub max_height: usize,
+    /// Offsets of blocks that underflow on every path reaching them, only known and so only
+    /// filled in if the analysis is `complete` and no jump is unresolved.
+    pub underflows: Vec<usize>,
+    /// Offsets of blocks that overflow on every path reaching them, filled in like `underflows`.
+    pub overflows: Vec<usize>,
+    /// Offsets of jumps whose target is not a pushed `JUMPDEST` on some path, which are not
+    /// followed.
+    pub unresolved_jumps: Vec<usize>,
+    /// Whether all paths were followed, false if a block was entered with too many stacks.
+    pub complete: bool,
+}
+
+/// Stack item, the offset if it is a pushed `JUMPDEST`.
+type Item = Option<usize>;
+
+enum Exit {
+    Underflow,
+    Overflow,
+    /// Blocks continued at, `None` for a jump to an unknown target.
+    Continue(Vec<Option<usize>>),
+}
+
+impl StackAnalysis {
+    pub fn new(code: &BytecodeView) -> Self {
+        let blocks = basic_blocks(code);
+        let index = blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| (block.start, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut analysis = Self {
+            entry_heights: vec![None; blocks.len()],
+            complete: true,
+            ..Default::default()
+        };
+        // per block whether some path got through, and whether one underflowed
+        let mut passed = vec![false; blocks.len()];
+        let mut underflowed = vec![false; blocks.len()];
+        let mut unresolved = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut states = vec![0; blocks.len()];
+        let mut queue = vec![];
+        if !blocks.is_empty() {
+            queue.push((0, vec![]));
+        }
+        while let Some((i, mut stack)) = queue.pop() {
+            if !visited.insert((i, stack.clone())) {
+                continue;
+            }
+            states[i] += 1;
+            if states[i] > MAX_BLOCK_STATES {
+                analysis.complete = false;
+                continue;
+            }
+            let block = &blocks[i];
+            let height = stack.len();
+            analysis.entry_heights[i] = Some(match analysis.entry_heights[i] {
+                Some((lo, hi)) => (lo.min(height), hi.max(height)),
+                None => (height, height),
+            });
+            match run(code, block, &mut stack, &mut analysis.max_height) {
+                Exit::Underflow => underflowed[i] = true,
+                Exit::Overflow => {}
+                Exit::Continue(targets) => {
+                    passed[i] = true;
+                    for target in targets {
+                        match target {
+                            Some(target) => queue.push((index[&target], stack.clone())),
+                            None => {
+                                unresolved.insert(block.end - 1);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // a path not followed may get through any block
+        let all_paths = analysis.complete && unresolved.is_empty();
+        for (i, block) in blocks.iter().enumerate() {
+            if all_paths && analysis.entry_heights[i].is_some() && !passed[i] {
+                match underflowed[i] {
+                    true => analysis.underflows.push(block.start),
+                    false => analysis.overflows.push(block.start),
+                }
+            }
+        }
+        analysis.unresolved_jumps = unresolved.into_iter().collect();
+        analysis.unresolved_jumps.sort_unstable();
+        analysis.blocks = blocks;
+        analysis
+    }
+}
+
+/// Run `block` on `stack`, returning where execution continues.
+fn run(code: &BytecodeView, block: &BasicBlock, stack: &mut Vec<Item>, max: &mut usize) -> Exit {
+    let mut targets = vec![];
+    let instructions = Instructions::new(&code.code()[block.start..block.end]);
+    let mut last = None;
+    for (_, opcode, immediate) in instructions {
+        last = Some(opcode);
+        let (inputs, outputs) = opcode.stack_effect();
+        if stack.len() < inputs {
+            return Exit::Underflow;
+        }
+        match opcode {
+            op if op.is_push() => stack.push(jumpdest(code, immediate)),
+            op if op.is_dup() => stack.push(stack[stack.len() - inputs]),
+            op if op.is_swap() => {
+                let top = stack.len() - 1;
+                stack.swap(top, top + 1 - inputs)
+            }
+            OpcodeId::JUMP | OpcodeId::JUMPI => {
+                targets.push(stack.pop().unwrap());
+                stack.truncate(stack.len() + 1 - inputs);
+                if opcode == OpcodeId::JUMPI && block.end < code.code().len() {
+                    targets.push(Some(block.end));
+                }
+            }
+            _ => {
+                stack.truncate(stack.len() - inputs);
+                stack.extend(std::iter::repeat_n(None, outputs));
+            }
+        }
+        if stack.len() > STACK_LIMIT {
+            return Exit::Overflow;
+        }
+        *max = (*max).max(stack.len());
+    }
+    // split before a JUMPDEST, execution falls through into it
+    if !last.is_some_and(ends_block) && block.end < code.code().len() {
+        targets.push(Some(block.end));
+    }
+    Exit::Continue(targets)
+}
+
+/// Whether `opcode` ends a basic block, by jumping or halting.
+fn ends_block(opcode: OpcodeId) -> bool {
+    matches!(
+        opcode,
+        OpcodeId::JUMP
+            | OpcodeId::JUMPI
+            | OpcodeId::STOP
+            | OpcodeId::RETURN
+            | OpcodeId::REVERT
+            | OpcodeId::INVALID(_)
+            | OpcodeId::SELFDESTRUCT
+    )
+}
+
+/// The pushed value if it is the offset of a `JUMPDEST`.
+fn jumpdest(code: &BytecodeView, immediate: &[u8]) -> Item {
+    immediate
+        .iter()
+        .try_fold(0usize, |acc, b| {
+            acc.checked_mul(256).map(|acc| acc + *b as usize)
+        })
+        .filter(|target| code.is_valid_jumpdest(*target))
+}
+
+/// Split code into basic blocks, a block starts at a `JUMPDEST` and ends after a jump or a
+/// halting instruction.
+pub fn basic_blocks(code: &BytecodeView) -> Vec<BasicBlock> {
+    let mut blocks = vec![];
+    let mut current: Option<BasicBlock> = None;
+    let mut push: Option<&[u8]> = None;
+    for (pc, opcode, immediate) in code.instructions() {
+        let next = pc + 1 + immediate.len();
+        if opcode == OpcodeId::JUMPDEST {
+            if let Some(mut block) = current.take() {
+                block.successors.push(pc);
+                blocks.push(block);
+            }
+        }
+        let block = current.get_or_insert_with(|| BasicBlock {
+            start: pc,
+            end: pc,
+            min_height: 0,
+            max_height: 0,
+            delta: 0,
+            successors: vec![],
+            dynamic_jump: false,
+        });
+        let (inputs, outputs) = opcode.stack_effect();
+        block.delta -= inputs as isize;
+        block.min_height = block.min_height.min(block.delta);
+        block.delta += outputs as isize;
+        block.max_height = block.max_height.max(block.delta);
+        block.end = next;
+
+        if matches!(opcode, OpcodeId::JUMP | OpcodeId::JUMPI) {
+            match push {
+                // a static jump to an invalid target fails, it has no successor
+                Some(target) => block.successors.extend(jumpdest(code, target)),
+                None => block.dynamic_jump = true,
+            }
+            if opcode == OpcodeId::JUMPI && next < code.code().len() {
+                block.successors.push(next);
+            }
+        }
+        push = opcode.is_push().then_some(immediate);
+        if ends_block(opcode) {
+            blocks.extend(current.take());
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+/// Deepest stack item reached by `DUPn` and `SWAPn`, the `n` of the largest one used.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StackDepths {
+    pub max_dup: usize,
+    pub max_swap: usize,
+}
+
+impl StackDepths {
+    /// Record an instruction, including the EOF `DUPN`, `SWAPN` and `EXCHANGE`.
+    pub fn record(&mut self, opcode: OpcodeId, immediate: &[u8]) {
+        match opcode {
+            op if op.is_dup() => self.max_dup = self.max_dup.max(op.postfix().unwrap() as usize),
+            op if op.is_swap() => self.max_swap = self.max_swap.max(op.postfix().unwrap() as usize),
+            OpcodeId::DUPN | OpcodeId::SWAPN | OpcodeId::EXCHANGE if immediate.is_empty() => {}
+            OpcodeId::DUPN => self.max_dup = self.max_dup.max(immediate[0] as usize + 1),
+            OpcodeId::SWAPN => self.max_swap = self.max_swap.max(immediate[0] as usize + 1),
+            // swaps items n + 1 and n + m + 1, as deep as SWAP(n + m)
+            OpcodeId::EXCHANGE => {
+                let depth = (immediate[0] >> 4) as usize + (immediate[0] & 0x0f) as usize + 2;
+                self.max_swap = self.max_swap.max(depth)
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::assemble;
+
+    fn analyze(source: &str) -> StackAnalysis {
+        let code = assemble(source).unwrap().to_bytes();
+        StackAnalysis::new(&BytecodeView::new(&code))
+    }
+
+    /// Entry heights of the block starting at `start`.
+    fn entry_height(analysis: &StackAnalysis, start: usize) -> Option<(usize, usize)> {
+        let i = analysis
+            .blocks
+            .iter()
+            .position(|block| block.start == start)
+            .unwrap();
+        analysis.entry_heights[i]
+    }
+
+    #[test]
+    fn internal_function_returns() {
+        let analysis = analyze(
+            "
+            PUSH ret1
+            PUSH func
+            JUMP
+            ret1:
+                JUMPDEST
+                PUSH ret2
+                PUSH func
+                JUMP
+            ret2:
+                JUMPDEST
+                STOP
+            func:
+                JUMPDEST
+                JUMP         ; back to the pushed return address
+            ",
+        );
+        assert!(analysis.complete);
+        assert!(analysis.unresolved_jumps.is_empty());
+        assert!(analysis.underflows.is_empty());
+        // ret1 at 5, ret2 at 11, func at 13
+        assert_eq!(entry_height(&analysis, 5), Some((0, 0)));
+        assert_eq!(entry_height(&analysis, 11), Some((0, 0)));
+        assert_eq!(entry_height(&analysis, 13), Some((1, 1)));
+        assert_eq!(analysis.max_height, 2);
+    }
+
+    #[test]
+    fn guaranteed_underflow() {
+        let analysis = analyze(
+            "
+            PUSH 1
+            ADD
+            STOP
+            ",
+        );
+        assert!(analysis.complete);
+        assert_eq!(analysis.underflows, [0]);
+        assert!(analysis.overflows.is_empty());
+    }
+
+    #[test]
+    fn unresolved_jump_hides_underflow() {
+        let analysis = analyze(
+            "
+            CALLDATASIZE
+            PUSH target
+            JUMPI
+            PUSH 1
+            PUSH 1
+            PUSH 0
+            CALLDATALOAD
+            JUMP         ; may reach target with 2 items
+            target:
+                JUMPDEST
+                ADD      ; underflows when reached by the JUMPI
+                STOP
+            ",
+        );
+        assert_eq!(analysis.unresolved_jumps, [10]);
+        assert!(analysis.underflows.is_empty());
+    }
+
+    #[test]
+    fn jumpi_fall_through() {
+        let analysis = analyze(
+            "
+            CALLDATASIZE
+            PUSH target
+            JUMPI
+            PUSH 1       ; fall-through
+            PUSH 2
+            STOP
+            target:
+                JUMPDEST
+                STOP
+            ",
+        );
+        assert_eq!(analysis.blocks[0].successors, [9, 4]);
+        assert_eq!(entry_height(&analysis, 4), Some((0, 0)));
+        assert_eq!(entry_height(&analysis, 9), Some((0, 0)));
+        assert_eq!(analysis.max_height, 2);
+        assert!(analysis.complete);
+    }
+
+    #[test]
+    fn recursion_hits_state_cap() {
+        // every iteration enters the loop with one more item
+        let analysis = analyze(
+            "
+            PUSH 1
+            loop:
+                JUMPDEST
+                PUSH 1
+                PUSH loop
+                JUMP
+            ",
+        );
+        assert!(!analysis.complete);
+        assert_eq!(entry_height(&analysis, 2), Some((1, MAX_BLOCK_STATES)));
+        assert!(analysis.overflows.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+//! Borrowed, allocation-free view of EVM byte code
+
+use crate::evm::bytecode::metadata_len;
+use crate::evm::eof::{is_eof, EofContainer, EofError};
+use crate::evm::opcode::OpcodeId;
+use std::cell::OnceCell;
+
+/// Instructions of a code slice as `(pc, opcode, immediate)`, the immediate is shorter than the
+/// push size if the code ends early.
+#[derive(Clone, Debug)]
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    pc: usize,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(code: &'a [u8]) -> Self {
+        Self { code, pc: 0 }
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = (usize, OpcodeId, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let byte = *self.code.get(self.pc)?;
+        let start = self.pc;
+        let end = (start + 1 + push_size(byte)).min(self.code.len());
+        self.pc = end;
+        Some((start, OpcodeId::from(byte), &self.code[start + 1..end]))
+    }
+}
+
+/// Number of push data bytes following `byte`, on raw bytes as this runs for every instruction.
+fn push_size(byte: u8) -> usize {
+    const PUSH1: u8 = OpcodeId::PUSH1.as_u8();
+    const PUSH32: u8 = OpcodeId::PUSH32.as_u8();
+    match byte {
+        PUSH1..=PUSH32 => (byte - PUSH1 + 1) as usize,
+        _ => 0,
+    }
+}
+
+/// Bit per code byte, set for push data, like geth's `codeBitmap`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CodeBitmap(Vec<u64>);
+
+impl CodeBitmap {
+    pub fn new(code: &[u8]) -> Self {
+        let mut bits = vec![0u64; code.len().div_ceil(64)];
+        let mut pc = 0;
+        while pc < code.len() {
+            let size = push_size(code[pc]);
+            pc += 1;
+            // push data past the end of code is not marked, there is nothing to jump to
+            for i in pc..(pc + size).min(code.len()) {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+            pc += size;
+        }
+        Self(bits)
+    }
+
+    /// Whether `pc` is an opcode rather than push data, false past the end of code.
+    pub fn is_code(&self, pc: usize) -> bool {
+        self.0
+            .get(pc / 64)
+            .is_some_and(|word| word & (1 << (pc % 64)) == 0)
+    }
+}
+
+/// A jump to a pushed constant, see [`BytecodeView::static_jumps`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StaticJump<'a> {
+    /// Offset of the `JUMP` or `JUMPI`.
+    pub pc: usize,
+    pub opcode: OpcodeId,
+    /// Pushed target, empty for `PUSH0`.
+    pub target: &'a [u8],
+}
+
+impl StaticJump<'_> {
+    /// Target offset, `None` if it does not fit in a `usize` and so is past any code.
+    pub fn target(&self) -> Option<usize> {
+        let start = self
+            .target
+            .iter()
+            .position(|b| *b != 0)
+            .unwrap_or(self.target.len());
+        let target = &self.target[start..];
+        (target.len() <= std::mem::size_of::<usize>())
+            .then(|| target.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    }
+}
+
+/// Code borrowed from storage, the trailing CBOR metadata split off like [`Bytecode`] does.
+///
+/// [`Bytecode`]: crate::evm::Bytecode
+#[derive(Clone, Debug)]
+pub struct BytecodeView<'a> {
+    bytes: &'a [u8],
+    code_len: usize,
+    /// Built on the first jumpdest query, most analyses only iterate instructions.
+    bitmap: OnceCell<CodeBitmap>,
+}
+
+impl<'a> BytecodeView<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let code_len = bytes.len() - metadata_len(bytes);
+        Self {
+            bytes,
+            code_len,
+            bitmap: OnceCell::new(),
+        }
+    }
+
+    /// All bytes, metadata included.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Code without metadata.
+    pub fn code(&self) -> &'a [u8] {
+        &self.bytes[..self.code_len]
+    }
+
+    /// Trailing CBOR metadata including its 2 byte length, empty if there is none.
+    pub fn metadata(&self) -> &'a [u8] {
+        &self.bytes[self.code_len..]
+    }
+
+    pub fn instructions(&self) -> Instructions<'a> {
+        Instructions::new(self.code())
+    }
+
+    /// `PUSHn target` directly followed by `JUMP` or `JUMPI` in reachable code.
+    ///
+    /// Code after `STOP`, `RETURN`, `REVERT`, `INVALID`, `JUMP` or `SELFDESTRUCT` is only
+    /// reachable from a `JUMPDEST`, which keeps data appended after the code from producing
+    /// jumps that never run. EOF code has no `JUMP` or `JUMPI` and so none.
+    pub fn static_jumps(&self) -> impl Iterator<Item = StaticJump<'a>> + '_ {
+        let mut reachable = true;
+        let mut push = None;
+        let legacy = (!is_eof(self.bytes)).then(|| self.instructions());
+        legacy
+            .into_iter()
+            .flatten()
+            .filter_map(move |(pc, opcode, immediate)| {
+                if opcode == OpcodeId::JUMPDEST {
+                    reachable = true;
+                }
+                let jump = match (opcode, push) {
+                    (OpcodeId::JUMP | OpcodeId::JUMPI, Some(target)) if reachable => {
+                        Some(StaticJump { pc, opcode, target })
+                    }
+                    _ => None,
+                };
+                push = opcode.is_push().then_some(immediate);
+                if matches!(
+                    opcode,
+                    OpcodeId::STOP
+                        | OpcodeId::RETURN
+                        | OpcodeId::REVERT
+                        | OpcodeId::INVALID(_)
+                        | OpcodeId::JUMP
+                        | OpcodeId::SELFDESTRUCT
+                ) {
+                    reachable = false;
+                }
+                jump
+            })
+    }
+
+    /// Static jumps whose target is not a valid `JUMPDEST`, each one fails whenever it runs.
+    pub fn invalid_static_jumps(&self) -> impl Iterator<Item = StaticJump<'a>> + '_ {
+        self.static_jumps().filter(|jump| {
+            !jump
+                .target()
+                .is_some_and(|target| self.is_valid_jumpdest(target))
+        })
+    }
+
+    /// The EOF container, `None` for legacy code.
+    pub fn eof(&self) -> Option<Result<EofContainer<'a>, EofError>> {
+        is_eof(self.bytes).then(|| EofContainer::parse(self.bytes))
+    }
+
+    /// Whether `pc` is an opcode rather than push data or metadata.
+    pub fn is_code(&self, pc: usize) -> bool {
+        self.bitmap
+            .get_or_init(|| CodeBitmap::new(self.code()))
+            .is_code(pc)
+    }
+
+    /// Whether `pc` is a `JUMPDEST` opcode, and so a valid jump target.
+    pub fn is_valid_jumpdest(&self, pc: usize) -> bool {
+        self.code().get(pc) == Some(&OpcodeId::JUMPDEST.as_u8()) && self.is_code(pc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::assemble;
+
+    #[test]
+    fn static_jumps() {
+        let code = assemble("PUSH 4\nJUMP\nSTOP\nJUMPDEST\nPUSH 3\nJUMPI")
+            .unwrap()
+            .to_bytes();
+        let view = BytecodeView::new(&code);
+        let targets = view
+            .static_jumps()
+            .map(|jump| (jump.pc, jump.target()))
+            .collect::<Vec<_>>();
+        assert_eq!(targets, [(2, Some(4)), (7, Some(3))]);
+        assert_eq!(
+            view.invalid_static_jumps()
+                .map(|jump| jump.pc)
+                .collect::<Vec<_>>(),
+            [7]
+        );
+    }
+
+    #[test]
+    fn no_static_jumps_in_eof() {
+        // one code section of PUSH1 3, JUMP, which EOF does not define
+        let code = [
+            0xef, 0x00, 0x01, 0x01, 0x00, 0x04, 0x02, 0x00, 0x01, 0x00, 0x03, 0xff, 0x00, 0x00,
+            0x00, 0x00, 0x80, 0x00, 0x01, 0x60, 0x03, 0x56,
+        ];
+        assert_eq!(BytecodeView::new(&code).static_jumps().count(), 0);
+    }
+}
@@ -0,0 +1,150 @@
+//! Offline execution of contract init code in an embedded EVM.
+//!
+//! The init code runs against an empty state, so constructors reading other accounts see empty
+//! code and zero balances, and constructors relying on them may behave differently than on chain.
+
+use crate::chain::{ChainProfile, Fork};
+use revm::interpreter::Interpreter;
+use revm::primitives::{AccountInfo, Address, ExecutionResult, Output, SpecId, TxKind, U256};
+use revm::{
+    db::CacheDB, db::EmptyDB, inspector_handle_register, Database, Evm, EvmContext, Inspector,
+};
+
+/// Gas available to the constructor, a mainnet block gas limit.
+const GAS_LIMIT: u64 = 30_000_000;
+/// Funded sender of the deployment.
+const DEPLOYER: Address = Address::repeat_byte(0xde);
+
+/// Result of executing init code.
+#[derive(Debug)]
+pub struct Execution {
+    /// Whether the constructor returned successfully.
+    pub success: bool,
+    pub gas_used: u64,
+    /// Code returned by the constructor, empty if it failed.
+    pub runtime_code: Vec<u8>,
+    /// Executed instructions per opcode, including nested calls and creations.
+    pub opcode_counts: [u64; 256],
+}
+
+/// Inspector counting executed opcodes.
+struct OpcodeCounter {
+    counts: [u64; 256],
+}
+
+impl<DB: Database> Inspector<DB> for OpcodeCounter {
+    fn step(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        self.counts[interp.current_opcode() as usize] += 1;
+    }
+}
+
+/// EVM rules of the chain in the block, the Merge for blocks before Shanghai.
+///
+/// The block's timestamp may be unknown, timestamp activated forks are then assumed active.
+pub fn spec_id(profile: &ChainProfile, block_number: u64, timestamp: Option<u64>) -> SpecId {
+    if profile.is_active(Fork::Cancun, block_number, timestamp) {
+        SpecId::CANCUN
+    } else if profile.is_active(Fork::Shanghai, block_number, timestamp) {
+        SpecId::SHANGHAI
+    } else {
+        SpecId::MERGE
+    }
+}
+
+/// Deploy `init_code` in a fresh EVM following the rules of `spec_id`.
+pub fn execute_init_code(init_code: &[u8], spec_id: SpecId) -> anyhow::Result<Execution> {
+    let mut db = CacheDB::new(EmptyDB::default());
+    db.insert_account_info(
+        DEPLOYER,
+        AccountInfo {
+            balance: U256::MAX,
+            ..Default::default()
+        },
+    );
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .with_external_context(OpcodeCounter { counts: [0; 256] })
+        .with_spec_id(spec_id)
+        .modify_block_env(|block| block.gas_limit = U256::from(GAS_LIMIT))
+        .modify_tx_env(|tx| {
+            tx.caller = DEPLOYER;
+            tx.transact_to = TxKind::Create;
+            tx.data = init_code.to_vec().into();
+            tx.gas_limit = GAS_LIMIT;
+            tx.gas_price = U256::ZERO;
+        })
+        .append_handler_register(inspector_handle_register)
+        .build();
+    let result = evm
+        .transact()
+        .map_err(|e| anyhow::anyhow!("evm error: {e:?}"))?
+        .result;
+
+    let (success, runtime_code) = match &result {
+        ExecutionResult::Success {
+            output: Output::Create(code, _),
+            ..
+        } => (true, code.to_vec()),
+        _ => (false, vec![]),
+    };
+    Ok(Execution {
+        success,
+        gas_used: result.gas_used(),
+        runtime_code,
+        opcode_counts: evm.context.external.counts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::Chain;
+    use crate::evm::{assemble, OpcodeId};
+
+    /// Init code returning `runtime` as the contract's code.
+    fn deployer(runtime: &[u8]) -> Vec<u8> {
+        let source = format!(
+            "
+            PUSH {}          ; size
+            DUP1
+            PUSH runtime     ; offset
+            PUSH 0           ; destination
+            CODECOPY
+            PUSH 0
+            RETURN
+            runtime:
+            ",
+            runtime.len()
+        );
+        let mut init_code = assemble(&source).unwrap().to_bytes();
+        init_code.extend_from_slice(runtime);
+        init_code
+    }
+
+    #[test]
+    fn returns_runtime_code() {
+        let runtime = assemble("PUSH 0x2a\nPUSH 0\nMSTORE\nPUSH 0x20\nPUSH 0\nRETURN")
+            .unwrap()
+            .to_bytes();
+        let execution = execute_init_code(&deployer(&runtime), SpecId::CANCUN).unwrap();
+        assert!(execution.success);
+        assert_eq!(execution.runtime_code, runtime);
+        assert_eq!(
+            execution.opcode_counts[OpcodeId::CODECOPY.as_u8() as usize],
+            1
+        );
+    }
+
+    #[test]
+    fn push0_before_shanghai() {
+        let init_code = deployer(&[OpcodeId::STOP.as_u8()]);
+        let polygon = Chain::Polygon.profile();
+        // the deployer pushes zeros with PUSH0
+        let spec = spec_id(polygon, 50522999, None);
+        assert_eq!(spec, SpecId::MERGE);
+        assert!(!execute_init_code(&init_code, spec).unwrap().success);
+        let spec = spec_id(polygon, 50523000, None);
+        assert_eq!(spec, SpecId::SHANGHAI);
+        assert!(execute_init_code(&init_code, spec).unwrap().success);
+    }
+}
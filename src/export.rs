@@ -0,0 +1,922 @@
+//! Export of the recorded tables to Parquet or CSV files.
+
+use crate::analysis::AnalyzerKind;
+use crate::report::{bucketed_opcode_counts, BlockRange, Bucket, Statistics};
+use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use futures::TryStreamExt;
+use parquet::arrow::ArrowWriter;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnection, SqliteRow};
+use sqlx::{Row, SqlitePool, ValueRef};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Rows buffered per Parquet record batch.
+const BATCH_SIZE: usize = 65536;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Table {
+    OpcodeStatistics,
+    ExecutedOpcodeStatistics,
+    ContractCalls,
+    ContractOpcodeStatistics,
+    Blocks,
+    Opcodes,
+    EofOpcodeStatistics,
+    ContractEofOpcodeStatistics,
+    EofContracts,
+    InvalidJumps,
+    ContractStackDepths,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Parquet,
+    Csv,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum ColumnType {
+    Integer,
+    Text,
+    /// Blob exported as `0x` prefixed hex text.
+    Hex,
+}
+
+#[derive(Clone)]
+pub struct Column {
+    pub name: &'static str,
+    pub ty: ColumnType,
+    pub description: &'static str,
+    pub nullable: bool,
+}
+
+const fn column(name: &'static str, ty: ColumnType, description: &'static str) -> Column {
+    Column {
+        name,
+        ty,
+        description,
+        nullable: false,
+    }
+}
+
+const fn nullable(column: Column) -> Column {
+    Column {
+        nullable: true,
+        ..column
+    }
+}
+
+const CHAIN_ID: Column = column("chain_id", ColumnType::Integer, "Chain id");
+const BLOCK_NUMBER: Column = column("block_number", ColumnType::Integer, "Block number");
+const OPCODE: Column = column("opcode", ColumnType::Integer, "Opcode byte, 0-255");
+const ADDRESS: Column = column("address", ColumnType::Hex, "Contract address");
+const DEPLOYED_COUNT: Column = column(
+    "count",
+    ColumnType::Integer,
+    "Occurrences in the deployed code",
+);
+
+const OPCODE_STATISTICS: &[Column] = &[CHAIN_ID, BLOCK_NUMBER, OPCODE, DEPLOYED_COUNT];
+const EXECUTED_OPCODE_STATISTICS: &[Column] = &[
+    CHAIN_ID,
+    BLOCK_NUMBER,
+    OPCODE,
+    column("count", ColumnType::Integer, "Executions"),
+    column("gas", ColumnType::Integer, "Gas spent on the executions"),
+];
+const CONTRACT_CALLS: &[Column] = &[
+    CHAIN_ID,
+    BLOCK_NUMBER,
+    ADDRESS,
+    column("count", ColumnType::Integer, "Calls in the block"),
+];
+const CONTRACT_OPCODE_STATISTICS: &[Column] = &[CHAIN_ID, ADDRESS, OPCODE, DEPLOYED_COUNT];
+const BLOCKS: &[Column] = &[
+    CHAIN_ID,
+    BLOCK_NUMBER,
+    column(
+        "timestamp",
+        ColumnType::Integer,
+        "Unix timestamp of the block",
+    ),
+];
+const OPCODES: &[Column] = &[
+    column("value", ColumnType::Integer, "Opcode byte, 0-255"),
+    column("name", ColumnType::Text, "Mnemonic"),
+];
+const EOF_CONTRACTS: &[Column] = &[
+    CHAIN_ID,
+    ADDRESS,
+    column("version", ColumnType::Integer, "EOF version"),
+    column(
+        "code_sections",
+        ColumnType::Integer,
+        "Number of code sections",
+    ),
+    column(
+        "container_sections",
+        ColumnType::Integer,
+        "Number of subcontainers",
+    ),
+    column(
+        "data_size",
+        ColumnType::Integer,
+        "Data size declared in the header",
+    ),
+    nullable(column(
+        "error",
+        ColumnType::Text,
+        "Why the container does not validate, null if it does",
+    )),
+];
+const INVALID_JUMPS: &[Column] = &[
+    CHAIN_ID,
+    ADDRESS,
+    column("pc", ColumnType::Integer, "Offset of the `JUMP` or `JUMPI`"),
+    column("target", ColumnType::Hex, "Pushed jump target"),
+];
+const CONTRACT_STACK_DEPTHS: &[Column] = &[
+    CHAIN_ID,
+    ADDRESS,
+    column("max_dup", ColumnType::Integer, "Deepest `DUPn`, 0 if none"),
+    column(
+        "max_swap",
+        ColumnType::Integer,
+        "Deepest `SWAPn`, 0 if none",
+    ),
+    nullable(column(
+        "max_height",
+        ColumnType::Integer,
+        "Maximum stack height, null for EOF containers that do not validate",
+    )),
+    column(
+        "underflow_blocks",
+        ColumnType::Integer,
+        "Blocks underflowing on every path, 0 unless `complete` and no jump is unresolved",
+    ),
+    column(
+        "overflow_blocks",
+        ColumnType::Integer,
+        "Blocks overflowing on every path, 0 unless `complete` and no jump is unresolved",
+    ),
+    column(
+        "unresolved_jumps",
+        ColumnType::Integer,
+        "Jumps whose target is not a pushed constant",
+    ),
+    column(
+        "complete",
+        ColumnType::Integer,
+        "1 unless paths were cut off at the cap on stacks per block",
+    ),
+];
+const BUCKETED_OPCODE_STATISTICS: &[Column] = &[
+    CHAIN_ID,
+    column(
+        "bucket",
+        ColumnType::Text,
+        "Start of the UTC time bucket, e.g. `2023-05-01` for the week starting on that Monday",
+    ),
+    OPCODE,
+    column("name", ColumnType::Text, "Mnemonic"),
+    column(
+        "count",
+        ColumnType::Integer,
+        "Count of the statistics in the bucket",
+    ),
+];
+
+impl Table {
+    pub const ALL: [Table; 11] = [
+        Table::OpcodeStatistics,
+        Table::ExecutedOpcodeStatistics,
+        Table::ContractCalls,
+        Table::ContractOpcodeStatistics,
+        Table::Blocks,
+        Table::Opcodes,
+        Table::EofOpcodeStatistics,
+        Table::ContractEofOpcodeStatistics,
+        Table::EofContracts,
+        Table::InvalidJumps,
+        Table::ContractStackDepths,
+    ];
+
+    /// Name of the SQL table, also used for the exported files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::OpcodeStatistics => "opcode_statistics",
+            Table::ExecutedOpcodeStatistics => "executed_opcode_statistics",
+            Table::ContractCalls => "contract_calls",
+            Table::ContractOpcodeStatistics => "contract_opcode_statistics",
+            Table::Blocks => "blocks",
+            Table::Opcodes => "opcode",
+            Table::EofOpcodeStatistics => "eof_opcode_statistics",
+            Table::ContractEofOpcodeStatistics => "contract_eof_opcode_statistics",
+            Table::EofContracts => "eof_contracts",
+            Table::InvalidJumps => "invalid_jumps",
+            Table::ContractStackDepths => "contract_stack_depths",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Table::OpcodeStatistics => "Opcodes of contracts deployed per block.",
+            Table::ExecutedOpcodeStatistics => "Opcodes executed by the txs of each block.",
+            Table::ContractCalls => "Top-level calls with input per contract and block.",
+            Table::ContractOpcodeStatistics => "Opcode histogram of each deployed contract.",
+            Table::Blocks => "Timestamps of the processed blocks.",
+            Table::Opcodes => "Mnemonics of the opcodes.",
+            Table::EofOpcodeStatistics => "EOF opcodes of contracts deployed per block.",
+            Table::ContractEofOpcodeStatistics => "EOF opcode histogram of each deployed contract.",
+            Table::EofContracts => "Shape and validation result of each EOF container.",
+            Table::InvalidJumps => "Static jumps to offsets that are not a `JUMPDEST`.",
+            Table::ContractStackDepths => "Stack heights and depths of each deployed contract.",
+        }
+    }
+
+    pub fn columns(self) -> &'static [Column] {
+        match self {
+            Table::OpcodeStatistics => OPCODE_STATISTICS,
+            Table::ExecutedOpcodeStatistics => EXECUTED_OPCODE_STATISTICS,
+            Table::ContractCalls => CONTRACT_CALLS,
+            Table::ContractOpcodeStatistics => CONTRACT_OPCODE_STATISTICS,
+            Table::Blocks => BLOCKS,
+            Table::Opcodes => OPCODES,
+            Table::EofOpcodeStatistics => OPCODE_STATISTICS,
+            Table::ContractEofOpcodeStatistics => CONTRACT_OPCODE_STATISTICS,
+            Table::EofContracts => EOF_CONTRACTS,
+            Table::InvalidJumps => INVALID_JUMPS,
+            Table::ContractStackDepths => CONTRACT_STACK_DEPTHS,
+        }
+    }
+
+    /// Columns identifying a row besides the chain id, those of deleted rows are exported
+    /// incrementally.
+    pub fn key(self) -> &'static [&'static str] {
+        match self {
+            Table::OpcodeStatistics
+            | Table::ExecutedOpcodeStatistics
+            | Table::EofOpcodeStatistics => &["block_number", "opcode"],
+            Table::ContractCalls => &["block_number", "address"],
+            Table::ContractOpcodeStatistics | Table::ContractEofOpcodeStatistics => {
+                &["address", "opcode"]
+            }
+            Table::Blocks => &["block_number"],
+            Table::EofContracts | Table::ContractStackDepths => &["address"],
+            Table::InvalidJumps => &["address", "pc"],
+            // only written by migrations
+            Table::Opcodes => &[],
+        }
+    }
+
+    /// Chain id and key columns, the columns of exported deletions.
+    pub fn key_columns(self) -> Vec<Column> {
+        self.columns()
+            .iter()
+            .filter(|c| c.name == CHAIN_ID.name || self.key().contains(&c.name))
+            .cloned()
+            .collect()
+    }
+
+    /// Column increasing with every insert and update of a row, the watermark of incremental
+    /// exports.
+    fn sequence(self) -> &'static str {
+        match self {
+            // only written by migrations
+            Table::Opcodes => "rowid",
+            _ => "seq",
+        }
+    }
+
+    /// Analyzer whose migrations create the table, which is missing until it ran once.
+    pub fn analyzer(self) -> Option<AnalyzerKind> {
+        match self {
+            Table::EofContracts => Some(AnalyzerKind::Eof),
+            Table::InvalidJumps => Some(AnalyzerKind::Jumps),
+            Table::ContractStackDepths => Some(AnalyzerKind::Stack),
+            _ => None,
+        }
+    }
+
+    /// Whether rows belong to a chain, so only the configured chain's are exported.
+    pub fn has_chain_id(self) -> bool {
+        self.columns().iter().any(|c| c.name == CHAIN_ID.name)
+    }
+
+    /// Whether rows belong to a block, so block range filters apply.
+    pub fn has_block_number(self) -> bool {
+        self.columns().iter().any(|c| c.name == BLOCK_NUMBER.name)
+    }
+}
+
+fn schema(columns: &[Column]) -> Schema {
+    Schema::new(
+        columns
+            .iter()
+            .map(|c| {
+                let ty = match c.ty {
+                    ColumnType::Integer => DataType::Int64,
+                    ColumnType::Text | ColumnType::Hex => DataType::Utf8,
+                };
+                let metadata =
+                    HashMap::from([("description".to_string(), c.description.to_string())]);
+                Field::new(c.name, ty, c.nullable).with_metadata(metadata)
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Highest exported sequence number per table, kept in the export directory.
+///
+/// Every insert and update of a row gives it the next sequence number, so everything above the
+/// watermark was added or changed since the last export.
+#[derive(Debug, Default)]
+pub struct Watermarks(BTreeMap<String, i64>);
+
+#[derive(Serialize, Deserialize)]
+struct WatermarksFile {
+    /// Missing in files of exports by rowid, which upgrades renumber.
+    #[serde(default)]
+    version: u32,
+    tables: BTreeMap<String, i64>,
+}
+
+impl Watermarks {
+    const FILE_NAME: &'static str = "watermarks.json";
+    /// Bumped whenever watermarks of earlier exports no longer match the rows.
+    const VERSION: u32 = 1;
+
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(Self::FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = serde_json::from_reader::<_, serde_json::Value>(File::open(&path)?)?;
+        let version = file["version"].as_u64().unwrap_or_default();
+        if version != Self::VERSION as u64 {
+            anyhow::bail!(
+                "{} holds watermarks of an older export that do not apply to the current \
+                 database, export incrementally into a new directory",
+                path.display()
+            );
+        }
+        Ok(Self(serde_json::from_value::<WatermarksFile>(file)?.tables))
+    }
+
+    pub fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        let file = File::create(dir.join(Self::FILE_NAME))?;
+        let watermarks = WatermarksFile {
+            version: Self::VERSION,
+            tables: self.0.clone(),
+        };
+        serde_json::to_writer_pretty(file, &watermarks)?;
+        Ok(())
+    }
+
+    pub fn get(&self, table: Table) -> Option<i64> {
+        self.0.get(table.name()).copied()
+    }
+
+    pub fn set(&mut self, table: Table, seq: i64) {
+        self.0.insert(table.name().to_string(), seq);
+    }
+}
+
+enum Value {
+    Integer(i64),
+    Text(String),
+    Null,
+}
+
+enum Writer {
+    Csv(BufWriter<File>),
+    Parquet {
+        writer: Box<ArrowWriter<File>>,
+        schema: SchemaRef,
+        rows: Vec<Vec<Value>>,
+    },
+}
+
+impl Writer {
+    fn new(columns: &[Column], format: Format, path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path)?;
+        Ok(match format {
+            Format::Csv => {
+                let mut writer = BufWriter::new(file);
+                let header = columns.iter().map(|c| c.name).collect::<Vec<_>>();
+                writeln!(writer, "{}", header.join(","))?;
+                Writer::Csv(writer)
+            }
+            Format::Parquet => {
+                let schema = Arc::new(schema(columns));
+                Writer::Parquet {
+                    writer: Box::new(ArrowWriter::try_new(file, schema.clone(), None)?),
+                    schema,
+                    rows: Vec::with_capacity(BATCH_SIZE),
+                }
+            }
+        })
+    }
+
+    fn push(&mut self, row: Vec<Value>) -> anyhow::Result<()> {
+        match self {
+            // values are numbers, mnemonics, hex or EOF errors, none need quoting
+            Writer::Csv(writer) => {
+                let mut line = String::new();
+                for (i, value) in row.iter().enumerate() {
+                    if i > 0 {
+                        line.push(',');
+                    }
+                    match value {
+                        Value::Integer(n) => write!(line, "{n}")?,
+                        Value::Text(s) => line.push_str(s),
+                        Value::Null => {}
+                    }
+                }
+                writeln!(writer, "{line}")?;
+            }
+            Writer::Parquet {
+                writer,
+                schema,
+                rows,
+            } => {
+                rows.push(row);
+                if rows.len() >= BATCH_SIZE {
+                    write_batch(writer, schema, rows)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        match self {
+            Writer::Csv(mut writer) => writer.flush()?,
+            Writer::Parquet {
+                mut writer,
+                schema,
+                mut rows,
+            } => {
+                if !rows.is_empty() {
+                    write_batch(&mut writer, &schema, &mut rows)?;
+                }
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_batch(
+    writer: &mut ArrowWriter<File>,
+    schema: &SchemaRef,
+    rows: &mut Vec<Vec<Value>>,
+) -> anyhow::Result<()> {
+    let columns = (0..schema.fields().len())
+        .map(|i| -> ArrayRef {
+            match schema.field(i).data_type() {
+                DataType::Int64 => {
+                    Arc::new(Int64Array::from_iter(rows.iter().map(|row| match row[i] {
+                        Value::Integer(n) => Some(n),
+                        Value::Null => None,
+                        Value::Text(_) => unreachable!(),
+                    })))
+                }
+                _ => Arc::new(StringArray::from_iter(rows.iter().map(|row| {
+                    match &row[i] {
+                        Value::Text(s) => Some(s.as_str()),
+                        Value::Null => None,
+                        Value::Integer(_) => unreachable!(),
+                    }
+                }))),
+            }
+        })
+        .collect::<Vec<_>>();
+    writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+    rows.clear();
+    Ok(())
+}
+
+pub struct ExportSummary {
+    pub rows: u64,
+    /// Highest exported sequence number, the next watermark.
+    pub last_seq: Option<i64>,
+}
+
+/// Export the rows of `table` of the chain added or changed after `after_seq`, all if `None`,
+/// within `range` to `path`.
+///
+/// The chain and range are ignored for tables without chain ids and block numbers.
+pub async fn export_table(
+    conn: &mut SqliteConnection,
+    chain_id: u64,
+    table: Table,
+    format: Format,
+    range: BlockRange,
+    after_seq: Option<i64>,
+    path: &Path,
+) -> anyhow::Result<ExportSummary> {
+    let columns = table.columns();
+    let mut sql = format!(
+        "SELECT {seq}, {} FROM {} WHERE {seq} > ?",
+        columns
+            .iter()
+            .map(|c| c.name)
+            .collect::<Vec<_>>()
+            .join(", "),
+        table.name(),
+        seq = table.sequence(),
+    );
+    if table.has_chain_id() {
+        sql.push_str(" AND chain_id = ?");
+    }
+    if table.has_block_number() {
+        sql.push_str(" AND block_number >= ? AND block_number < ?");
+    }
+    sql.push_str(&format!(" ORDER BY {}", table.sequence()));
+    // the opcode table's rowids start at 0
+    let mut query = sqlx::query(&sql).bind(after_seq.unwrap_or(i64::MIN));
+    if table.has_chain_id() {
+        query = query.bind(chain_id as i64);
+    }
+    if table.has_block_number() {
+        let end = range.end.map(|n| n as i64).unwrap_or(i64::MAX);
+        query = query.bind(range.start as i64).bind(end);
+    }
+
+    let mut writer = Writer::new(columns, format, path)?;
+    let mut summary = ExportSummary {
+        rows: 0,
+        last_seq: None,
+    };
+    let mut rows = query.fetch(&mut *conn);
+    while let Some(row) = rows.try_next().await? {
+        writer.push(decode_row(&row, columns)?)?;
+        summary.rows += 1;
+        summary.last_seq = Some(row.try_get(0)?);
+    }
+    writer.finish()?;
+    Ok(summary)
+}
+
+/// Whether the SQL table of `table` exists, those of analyzers only once they ran.
+pub async fn table_exists(pool: &SqlitePool, table: Table) -> Result<bool, sqlx::Error> {
+    let (exists,): (bool,) = sqlx::query_as(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?)",
+    )
+    .bind(table.name())
+    .fetch_one(pool)
+    .await?;
+    Ok(exists)
+}
+
+/// Name of the file of `statistics` per `bucket`, without extension.
+pub fn bucketed_name(statistics: Statistics, bucket: Bucket) -> String {
+    format!("{}_by_{}", statistics.table(), bucket.name())
+}
+
+/// Export the opcode counts of `statistics` per time `bucket` within `range` to `path`,
+/// returning the number of rows.
+pub async fn export_buckets(
+    pool: &SqlitePool,
+    chain_id: u64,
+    statistics: Statistics,
+    range: BlockRange,
+    bucket: Bucket,
+    format: Format,
+    path: &Path,
+) -> anyhow::Result<u64> {
+    let rows = bucketed_opcode_counts(pool, chain_id, statistics, range, bucket, &[]).await?;
+    let mut writer = Writer::new(BUCKETED_OPCODE_STATISTICS, format, path)?;
+    for row in rows.iter() {
+        writer.push(vec![
+            Value::Integer(chain_id as i64),
+            Value::Text(row.bucket.clone()),
+            Value::Integer(row.opcode as i64),
+            Value::Text(row.name.clone()),
+            Value::Integer(row.count as i64),
+        ])?;
+    }
+    writer.finish()?;
+    Ok(rows.len() as u64)
+}
+
+/// Export the keys of rows of `table` of the chain deleted after `after_seq` to `path`.
+///
+/// Keys present again, because the row was deleted and inserted anew, are left out since the
+/// rows export carries the new row.
+pub async fn export_deletions(
+    conn: &mut SqliteConnection,
+    chain_id: u64,
+    table: Table,
+    format: Format,
+    after_seq: i64,
+    path: &Path,
+) -> anyhow::Result<ExportSummary> {
+    let key = table.key_columns();
+    let names = key.iter().map(|c| c.name).collect::<Vec<_>>();
+    let mut sql = format!(
+        "SELECT MAX(seq), {} FROM export_deletions AS deleted
+        WHERE table_name = ? AND seq > ? AND chain_id = ?",
+        names.join(", "),
+    );
+    sql.push_str(&format!(
+        " AND NOT EXISTS (SELECT 1 FROM {} AS present WHERE {})
+        GROUP BY {}
+        ORDER BY 1",
+        table.name(),
+        names
+            .iter()
+            .map(|name| format!("present.{name} = deleted.{name}"))
+            .collect::<Vec<_>>()
+            .join(" AND "),
+        names.join(", "),
+    ));
+    let query = sqlx::query(&sql)
+        .bind(table.name())
+        .bind(after_seq)
+        .bind(chain_id as i64);
+
+    let mut writer = Writer::new(&key, format, path)?;
+    let mut summary = ExportSummary {
+        rows: 0,
+        last_seq: None,
+    };
+    let mut rows = query.fetch(&mut *conn);
+    while let Some(row) = rows.try_next().await? {
+        writer.push(decode_row(&row, &key)?)?;
+        summary.rows += 1;
+        summary.last_seq = summary.last_seq.max(Some(row.try_get(0)?));
+    }
+    writer.finish()?;
+    Ok(summary)
+}
+
+fn decode_row(row: &SqliteRow, columns: &[Column]) -> Result<Vec<Value>, sqlx::Error> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            // column 0 is the sequence number
+            if c.nullable && row.try_get_raw(i + 1)?.is_null() {
+                return Ok(Value::Null);
+            }
+            Ok(match c.ty {
+                ColumnType::Integer => Value::Integer(row.try_get(i + 1)?),
+                ColumnType::Text => Value::Text(row.try_get(i + 1)?),
+                ColumnType::Hex => Value::Text(format!(
+                    "0x{}",
+                    hex::encode(row.try_get::<Vec<u8>, _>(i + 1)?)
+                )),
+            })
+        })
+        .collect()
+}
+
+/// Markdown documentation of the exported tables.
+pub fn schema_doc(format: Format) -> String {
+    let mut doc = String::from("# Exported tables\n\n");
+    let (int, text) = match format {
+        Format::Parquet => ("INT64", "UTF8"),
+        Format::Csv => ("integer", "text"),
+    };
+    let columns_doc = |doc: &mut String, columns: &[Column]| {
+        doc.push_str("\n\n| column | type | description |\n| --- | --- | --- |\n");
+        for c in columns {
+            let ty = match c.ty {
+                ColumnType::Integer => int,
+                ColumnType::Text | ColumnType::Hex => text,
+            };
+            doc.push_str(&format!("| {} | {} | {} |\n", c.name, ty, c.description));
+        }
+        doc.push('\n');
+    };
+    for table in Table::ALL {
+        doc.push_str(&format!("## {}\n\n{}", table.name(), table.description()));
+        if table.has_chain_id() {
+            doc.push_str(" Only rows of the configured `chain`.");
+        }
+        if table.has_block_number() {
+            doc.push_str(" Filtered by `--range`.");
+        }
+        if table.analyzer().is_some() {
+            doc.push_str(" Only exported once its analyzer ran.");
+        }
+        if !table.key().is_empty() {
+            doc.push_str(&format!(
+                " Increments list rows deleted since the previous one in `{}_deletions`, by \
+                 their `{}` columns.",
+                table.name(),
+                table
+                    .key_columns()
+                    .iter()
+                    .map(|c| c.name)
+                    .collect::<Vec<_>>()
+                    .join("`, `")
+            ));
+        }
+        columns_doc(&mut doc, table.columns());
+    }
+    doc.push_str(
+        "## <statistics>_by_<bucket>\n\nOpcode counts of `--statistics` per `--bucket` of hour, \
+         day, week or month, e.g. `opcode_statistics_by_week`. Only blocks with a recorded \
+         timestamp of the configured `chain`, filtered by `--range`.",
+    );
+    columns_doc(&mut doc, BUCKETED_OPCODE_STATISTICS);
+    doc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::Registry;
+    use crate::db::{append_opcode_statistics, memory_sqlite, set_contract_opcode_statistics};
+    use ethers::types::Address;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("opcode-scan-{}-{}.csv", std::process::id(), name))
+    }
+
+    /// Export to a CSV file and read it back.
+    async fn export_csv(
+        pool: &SqlitePool,
+        table: Table,
+        after_seq: Option<i64>,
+        name: &str,
+    ) -> (ExportSummary, String) {
+        let path = temp_path(name);
+        let mut conn = pool.acquire().await.unwrap();
+        let summary = export_table(
+            &mut conn,
+            1,
+            table,
+            Format::Csv,
+            BlockRange::default(),
+            after_seq,
+            &path,
+        )
+        .await
+        .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        (summary, csv)
+    }
+
+    #[tokio::test]
+    async fn incremental() {
+        let pool = memory_sqlite().await;
+        append_opcode_statistics(&pool, 1, 1, 0x5f, 3)
+            .await
+            .unwrap();
+        append_opcode_statistics(&pool, 2, 1, 0x5f, 1)
+            .await
+            .unwrap();
+        let (summary, csv) = export_csv(&pool, Table::OpcodeStatistics, None, "full").await;
+        assert_eq!(summary.rows, 1);
+        assert_eq!(csv, "chain_id,block_number,opcode,count\n1,1,95,3\n");
+
+        // an update moves the row past the watermark
+        append_opcode_statistics(&pool, 1, 1, 0x5f, 2)
+            .await
+            .unwrap();
+        append_opcode_statistics(&pool, 1, 2, 0x01, 4)
+            .await
+            .unwrap();
+        let (summary, csv) = export_csv(
+            &pool,
+            Table::OpcodeStatistics,
+            summary.last_seq,
+            "increment",
+        )
+        .await;
+        assert_eq!(summary.rows, 2);
+        assert_eq!(
+            csv,
+            "chain_id,block_number,opcode,count\n1,1,95,5\n1,2,1,4\n"
+        );
+        let (summary, _) =
+            export_csv(&pool, Table::OpcodeStatistics, summary.last_seq, "empty").await;
+        assert_eq!(summary.rows, 0);
+    }
+
+    #[tokio::test]
+    async fn deletions() {
+        let pool = memory_sqlite().await;
+        let address = Address::repeat_byte(1);
+        set_contract_opcode_statistics(&pool, 1, address, &[(0x00, 1), (0x01, 2)], false)
+            .await
+            .unwrap();
+        let (summary, _) =
+            export_csv(&pool, Table::ContractOpcodeStatistics, None, "snapshot").await;
+        let after_seq = summary.last_seq.unwrap();
+
+        // the histogram is replaced, STOP is recorded anew and ADD is gone
+        set_contract_opcode_statistics(&pool, 1, address, &[(0x00, 3)], false)
+            .await
+            .unwrap();
+        let (_, csv) = export_csv(
+            &pool,
+            Table::ContractOpcodeStatistics,
+            Some(after_seq),
+            "replaced",
+        )
+        .await;
+        assert_eq!(
+            csv,
+            format!("chain_id,address,opcode,count\n1,{:?},0,3\n", address)
+        );
+
+        let path = temp_path("deletions");
+        let mut conn = pool.acquire().await.unwrap();
+        let summary = export_deletions(
+            &mut conn,
+            1,
+            Table::ContractOpcodeStatistics,
+            Format::Csv,
+            after_seq,
+            &path,
+        )
+        .await
+        .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(summary.rows, 1);
+        assert_eq!(csv, format!("chain_id,address,opcode\n1,{:?},1\n", address));
+    }
+
+    #[tokio::test]
+    async fn analyzer_tables() {
+        let pool = memory_sqlite().await;
+        Registry::from_kinds(&[AnalyzerKind::Eof, AnalyzerKind::Jumps])
+            .migrate(&pool)
+            .await
+            .unwrap();
+        let address = Address::repeat_byte(1);
+        sqlx::query("INSERT INTO eof_contracts VALUES (1, ?, 1, 2, 0, 4, NULL, 0)")
+            .bind(address.as_bytes())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (_, csv) = export_csv(&pool, Table::EofContracts, None, "eof").await;
+        assert_eq!(
+            csv,
+            format!(
+                "chain_id,address,version,code_sections,container_sections,data_size,error\n\
+                 1,{:?},1,2,0,4,\n",
+                address
+            )
+        );
+        // nulls are valid in Parquet too
+        let path = temp_path("eof-parquet");
+        export_table(
+            &mut pool.acquire().await.unwrap(),
+            1,
+            Table::EofContracts,
+            Format::Parquet,
+            BlockRange::default(),
+            None,
+            &path,
+        )
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        for pc in [3, 5] {
+            sqlx::query(
+                "INSERT INTO invalid_jumps (chain_id, address, pc, target) VALUES (1, ?, ?, X'ff')",
+            )
+            .bind(address.as_bytes())
+            .bind(pc)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        let (summary, _) = export_csv(&pool, Table::InvalidJumps, None, "jumps").await;
+        sqlx::query("DELETE FROM invalid_jumps WHERE pc = 5")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let path = temp_path("jump-deletions");
+        export_deletions(
+            &mut pool.acquire().await.unwrap(),
+            1,
+            Table::InvalidJumps,
+            Format::Csv,
+            summary.last_seq.unwrap(),
+            &path,
+        )
+        .await
+        .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(csv, format!("chain_id,address,pc\n1,{:?},5\n", address));
+    }
+}
@@ -0,0 +1,3 @@
+//! Byte code handling of opcode-scan, a library so the benchmarks can use it.
+
+pub mod evm;
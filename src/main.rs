@@ -1,16 +1,35 @@
 #[macro_use]
 extern crate tracing;
 
-use crate::consts::{HTTP_PROVIDER, METADATA_TREE, SLED_DB_PATH};
+use crate::analysis::Registry;
+use crate::chain::Chain;
+use crate::cli::{Cli, Command};
+use crate::config::Config;
+use crate::consts::METADATA_TREE;
 use crate::db::init_sqlite;
+use crate::provider::{HeadFollower, PoolProvider};
+use clap::Parser;
+use ethers::providers::Middleware;
+use opcode_scan::evm;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::EnvFilter;
 
+mod analysis;
+mod api;
+mod chain;
+mod cli;
+mod config;
 mod consts;
+mod corpus;
 mod db;
-mod evm;
+mod executor;
+mod export;
+mod metrics;
+mod progress;
 mod provider;
+mod report;
 mod tasks;
 
 #[tokio::main]
@@ -19,6 +38,33 @@ async fn main() -> anyhow::Result<()> {
         .with_env_filter(EnvFilter::builder().from_env_lossy())
         .init();
 
+    let cli = Cli::parse();
+    let mut config = Config::load(&cli.config)?;
+    if let Some(chain) = cli.chain {
+        config.chain = chain;
+    }
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run(config).await,
+        Command::Disasm(args) => cli::disasm::run(&config, args),
+        Command::Execute(args) => cli::execute::run(&config, args).await,
+        Command::Report(args) => cli::report::run(&config, args).await,
+        Command::Asm(args) => cli::asm::run(args),
+        Command::Corpus(args) => cli::corpus::run(&config, args),
+        Command::Export(args) => cli::export::run(&config, args).await,
+        Command::Reanalyze(args) => cli::reanalyze::run(&config, args).await,
+        Command::Status => cli::status::run(&config).await,
+        Command::Serve { listen } => {
+            let listen = listen
+                .or(config.api_listen)
+                .ok_or_else(|| anyhow::anyhow!("no listen address given"))?;
+            let router = api::router(init_sqlite().await?, config.chain.id());
+            api::serve(router, listen, running()?).await
+        }
+    }
+}
+
+/// Flag cleared on ctrl-c.
+fn running() -> anyhow::Result<Arc<AtomicBool>> {
     let running = Arc::new(AtomicBool::new(true));
 
     {
@@ -27,15 +73,56 @@ async fn main() -> anyhow::Result<()> {
             running.store(false, Ordering::SeqCst);
         })?;
     }
+    Ok(running)
+}
+
+/// Fail unless the nodes serve the configured chain.
+async fn check_chain_id(
+    chain: Chain,
+    provider: &PoolProvider,
+    follower: &HeadFollower,
+) -> anyhow::Result<()> {
+    let chain_ids = [
+        provider.get_chainid().await?.as_u64(),
+        follower.get_chain_id().await?,
+    ];
+    if let Some(chain_id) = chain_ids.into_iter().find(|id| *id != chain.id()) {
+        anyhow::bail!(
+            "node serves chain id {}, but {} with chain id {} is configured",
+            chain_id,
+            chain.profile().name,
+            chain.id()
+        );
+    }
+    Ok(())
+}
+
+async fn run(config: Config) -> anyhow::Result<()> {
+    let running = running()?;
+    let chain = config.chain;
+
+    let provider = provider::pool_provider(&config)?;
+    let follower = HeadFollower::new(&config, provider.clone()).await?;
+    check_chain_id(chain, &provider, &follower).await?;
+    info!(
+        "scanning {} from block #{}",
+        chain.profile().name,
+        config.start_block()
+    );
 
     let pool = init_sqlite().await?;
-    let sled_db = sled::open(SLED_DB_PATH)?;
+    let sled_db = sled::open(chain.sled_path())?;
+    let analyzers = Registry::from_kinds(&config.analyzers);
+    analyzers.migrate(&pool).await?;
+    let analyzers = Arc::new(analyzers);
 
     let mut join_handles = vec![];
     let listener = tokio::spawn(tasks::listen_blocks(
         pool.clone(),
+        chain.id(),
         sled_db.open_tree(METADATA_TREE)?,
-        provider::ws_provider().await?,
+        follower,
+        config.start_block(),
         running.clone(),
     ));
     join_handles.push(listener);
@@ -44,8 +131,11 @@ async fn main() -> anyhow::Result<()> {
         let worker = tokio::spawn(tasks::handle_block(
             i,
             pool.clone(),
+            chain.id(),
             sled_db.clone(),
-            provider::http_provider(HTTP_PROVIDER).await,
+            provider.clone(),
+            config.block_batch_size,
+            config.opcode_tracer.is_some(),
             running.clone(),
         ));
         join_handles.push(worker);
@@ -53,12 +143,51 @@ async fn main() -> anyhow::Result<()> {
     let worker = tokio::spawn(tasks::handle_tx(
         1,
         pool.clone(),
+        chain,
         sled_db.clone(),
-        provider::http_provider(HTTP_PROVIDER).await,
+        provider.clone(),
+        config.tx_batch_size,
+        config.code_sources.clone(),
+        analyzers,
         running.clone(),
     ));
     join_handles.push(worker);
 
+    if let Some(tracer) = config.opcode_tracer {
+        let worker = tokio::spawn(tasks::handle_trace(
+            2,
+            pool.clone(),
+            chain.id(),
+            provider.clone(),
+            tracer,
+            running.clone(),
+        ));
+        join_handles.push(worker);
+    }
+
+    join_handles.push(tokio::spawn(progress::track_progress(
+        pool.clone(),
+        chain.id(),
+        Duration::from_secs(config.progress_interval),
+        running.clone(),
+    )));
+    if let Some(listen) = config.api_listen {
+        let server = api::serve(
+            api::router(pool.clone(), chain.id()),
+            listen,
+            running.clone(),
+        );
+        join_handles.push(tokio::spawn(server));
+    }
+    if let Some(listen) = config.metrics_listen {
+        let server = api::serve(
+            metrics::router(pool.clone(), chain.id()),
+            listen,
+            running.clone(),
+        );
+        join_handles.push(tokio::spawn(server));
+    }
+
     futures::future::join_all(join_handles).await;
     Ok(())
 }
@@ -0,0 +1,111 @@
+//! Prometheus metrics of the scanner.
+
+use crate::db::get_progress;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, TextEncoder,
+};
+use sqlx::SqlitePool;
+use std::sync::LazyLock;
+
+pub struct Metrics {
+    pub blocks_processed: IntCounter,
+    pub tx_tasks_processed: IntCounter,
+    pub invalid_opcode_contracts: IntCounter,
+    pub invalid_jump_contracts: IntCounter,
+    /// Latest block seen on chain.
+    pub head_block: IntGauge,
+    /// Head block minus the latest block fetched by a block worker.
+    pub head_lag: IntGauge,
+    pub queue_depth: IntGaugeVec,
+    pub rpc_duration: HistogramVec,
+    pub rpc_errors: IntCounterVec,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| Metrics {
+    blocks_processed: register_int_counter!(
+        "opcode_scan_blocks_processed_total",
+        "Blocks processed by block workers"
+    )
+    .unwrap(),
+    tx_tasks_processed: register_int_counter!(
+        "opcode_scan_tx_tasks_processed_total",
+        "Create txs processed by tx workers"
+    )
+    .unwrap(),
+    invalid_opcode_contracts: register_int_counter!(
+        "opcode_scan_invalid_opcode_contracts_total",
+        "Deployed contracts containing invalid opcodes"
+    )
+    .unwrap(),
+    invalid_jump_contracts: register_int_counter!(
+        "opcode_scan_invalid_jump_contracts_total",
+        "Deployed contracts containing static jumps to invalid targets"
+    )
+    .unwrap(),
+    head_block: register_int_gauge!("opcode_scan_head_block", "Latest block on chain").unwrap(),
+    head_lag: register_int_gauge!(
+        "opcode_scan_head_lag_blocks",
+        "Blocks between the chain head and the latest processed block"
+    )
+    .unwrap(),
+    queue_depth: register_int_gauge_vec!(
+        "opcode_scan_queue_depth",
+        "Pending tasks per queue",
+        &["queue"]
+    )
+    .unwrap(),
+    rpc_duration: register_histogram_vec!(
+        "opcode_scan_rpc_duration_seconds",
+        "Latency of JSON-RPC requests including failover",
+        &["method"]
+    )
+    .unwrap(),
+    rpc_errors: register_int_counter_vec!(
+        "opcode_scan_rpc_errors_total",
+        "Failed JSON-RPC requests",
+        &["method"]
+    )
+    .unwrap(),
+});
+
+/// Metrics of the scanner of the chain.
+pub fn router(pool: SqlitePool, chain_id: u64) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state((pool, chain_id))
+}
+
+async fn metrics(
+    State((pool, chain_id)): State<(SqlitePool, u64)>,
+) -> Result<String, (StatusCode, String)> {
+    // queues live in sqlite, so their depths are sampled on scrape
+    let progress = get_progress(&pool, chain_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let queue_depth = &METRICS.queue_depth;
+    queue_depth
+        .with_label_values(&["block"])
+        .set(progress.block_tasks as i64);
+    queue_depth
+        .with_label_values(&["tx"])
+        .set(progress.tx_tasks as i64);
+    queue_depth
+        .with_label_values(&["trace"])
+        .set(progress.trace_tasks as i64);
+    if let Some(latest) = progress.latest_processed_block {
+        let head = METRICS.head_block.get();
+        METRICS.head_lag.set((head - latest as i64).max(0));
+    }
+
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(String::from_utf8(buffer).unwrap())
+}
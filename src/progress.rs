@@ -0,0 +1,97 @@
+//! Throughput and ETA of the scan.
+
+use crate::db::{get_progress, set_scan_rates, Progress, ScanRates};
+use crate::metrics::METRICS;
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Periodically log throughput and ETA, and persist the rates for the `status` command.
+///
+/// Rates are derived from the processed counters of this process, so they cover the workers of
+/// this scanner only.
+pub async fn track_progress(
+    pool: SqlitePool,
+    chain_id: u64,
+    interval: Duration,
+    running: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let mut last_time = Instant::now();
+    let mut last_blocks = METRICS.blocks_processed.get();
+    let mut last_tx_tasks = METRICS.tx_tasks_processed.get();
+    while running.load(Ordering::SeqCst) {
+        // short sleeps, so shutdown is not held up by a long interval
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        if last_time.elapsed() < interval {
+            continue;
+        }
+        let elapsed = last_time.elapsed().as_secs_f64();
+        let blocks = METRICS.blocks_processed.get();
+        let tx_tasks = METRICS.tx_tasks_processed.get();
+        let head_block = METRICS.head_block.get();
+        let rates = ScanRates {
+            head_block: (head_block > 0).then_some(head_block as u64),
+            blocks_per_second: (blocks - last_blocks) as f64 / elapsed,
+            tx_tasks_per_second: (tx_tasks - last_tx_tasks) as f64 / elapsed,
+            updated_at: unix_now(),
+        };
+        (last_time, last_blocks, last_tx_tasks) = (Instant::now(), blocks, tx_tasks);
+
+        let progress = get_progress(&pool, chain_id).await?;
+        log_progress(&progress, &rates);
+        set_scan_rates(&pool, chain_id, &rates).await?;
+    }
+    info!("gracefully shutdown");
+    Ok(())
+}
+
+fn log_progress(progress: &Progress, rates: &ScanRates) {
+    if let (Some(latest), Some(head)) = (progress.latest_processed_block, rates.head_block) {
+        info!("processed block #{} of #{}", latest, head);
+    }
+    info!(
+        "{:.2} blocks/s, {} block tasks remaining, ETA {}",
+        rates.blocks_per_second,
+        progress.block_tasks,
+        format_eta(progress.block_tasks, rates.blocks_per_second)
+    );
+    info!(
+        "{:.2} tx tasks/s, {} tx tasks remaining, ETA {}",
+        rates.tx_tasks_per_second,
+        progress.tx_tasks,
+        format_eta(progress.tx_tasks, rates.tx_tasks_per_second)
+    );
+}
+
+/// Time to work off `remaining` tasks at `rate` per second, e.g. `2d 3h 15m`.
+pub fn format_eta(remaining: u64, rate: f64) -> String {
+    if remaining == 0 {
+        return "done".to_string();
+    }
+    if rate <= 0.0 {
+        return "unknown".to_string();
+    }
+    format_duration(Duration::from_secs_f64(remaining as f64 / rate))
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes) = (secs / 86400, secs % 86400 / 3600, secs % 3600 / 60);
+    if days > 0 {
+        format!("{days}d {hours}h {minutes}m")
+    } else if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {}s", secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
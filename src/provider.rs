@@ -1,14 +1,109 @@
-use crate::consts::WS_PROVIDER;
+use crate::config::{Config, HeadFollowerKind};
 use ethers::prelude::*;
-use url::Url;
+use futures::stream::BoxStream;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
-pub async fn ws_provider() -> Result<Provider<Ws>, ProviderError> {
-    Provider::<Ws>::connect(WS_PROVIDER).await
+mod pool;
+
+pub use pool::{PoolClient, PoolError};
+
+pub type PoolProvider = Provider<PoolClient>;
+
+pub async fn ws_provider(url: &str) -> Result<Provider<Ws>, ProviderError> {
+    Provider::<Ws>::connect(url).await
+}
+
+/// Provider over all configured HTTP endpoints, with a background health check running.
+pub fn pool_provider(config: &Config) -> Result<PoolProvider, PoolError> {
+    let client = PoolClient::new(&config.endpoints())?;
+    client.spawn_health_check(Duration::from_secs(config.health_check_interval));
+    Ok(Provider::new(client))
 }
 
-pub async fn http_provider(url: &str) -> Provider<impl JsonRpcClient> {
-    let base_client = Http::new(url.parse::<Url>().unwrap());
-    let retry_client = RetryClientBuilder::default()
-        .build(base_client, Box::new(HttpRateLimitRetryPolicy::default()));
-    Provider::new(retry_client)
+/// Source of new chain heads for the block listener.
+pub enum HeadFollower {
+    /// Receive heads from a `newHeads` subscription.
+    Subscribe(Provider<Ws>),
+    /// Poll `eth_blockNumber`, works with HTTP-only endpoints.
+    Poll {
+        provider: PoolProvider,
+        interval: Duration,
+    },
+}
+
+impl HeadFollower {
+    pub async fn new(config: &Config, provider: PoolProvider) -> Result<Self, ProviderError> {
+        Ok(match config.head_follower {
+            HeadFollowerKind::Subscribe => Self::Subscribe(ws_provider(&config.ws_provider).await?),
+            HeadFollowerKind::Poll => Self::Poll {
+                provider,
+                interval: Duration::from_secs(config.poll_interval),
+            },
+        })
+    }
+
+    pub async fn get_chain_id(&self) -> Result<u64, ProviderError> {
+        let chain_id = match self {
+            Self::Subscribe(provider) => provider.get_chainid().await?,
+            Self::Poll { provider, .. } => provider.get_chainid().await?,
+        };
+        Ok(chain_id.as_u64())
+    }
+
+    pub async fn get_block_number(&self) -> Result<u64, ProviderError> {
+        let block_number = match self {
+            Self::Subscribe(provider) => provider.get_block_number().await?,
+            Self::Poll { provider, .. } => provider.get_block_number().await?,
+        };
+        Ok(block_number.as_u64())
+    }
+
+    /// Stream of new block numbers after `after`.
+    ///
+    /// The poll follower yields every block in between two polls, so no block is skipped even
+    /// if several arrive within one interval. It ends once `running` is cleared, which is
+    /// checked on every poll tick.
+    pub async fn new_blocks(
+        &self,
+        after: u64,
+        running: Arc<AtomicBool>,
+    ) -> Result<BoxStream<'_, u64>, ProviderError> {
+        match self {
+            Self::Subscribe(provider) => {
+                let stream = provider.subscribe_blocks().await?;
+                Ok(Box::pin(stream.filter_map(|block| async move {
+                    block.number.map(|n| n.as_u64())
+                })))
+            }
+            Self::Poll { provider, interval } => {
+                let state = (VecDeque::new(), after);
+                Ok(Box::pin(futures::stream::unfold(
+                    state,
+                    move |(mut pending, mut last)| {
+                        let running = running.clone();
+                        async move {
+                            while pending.is_empty() {
+                                tokio::time::sleep(*interval).await;
+                                if !running.load(Ordering::SeqCst) {
+                                    return None;
+                                }
+                                match provider.get_block_number().await {
+                                    Ok(latest) => {
+                                        let latest = latest.as_u64();
+                                        pending.extend((last + 1)..=latest);
+                                        last = last.max(latest);
+                                    }
+                                    Err(e) => warn!("failed to poll block number: {}", e),
+                                }
+                            }
+                            pending.pop_front().map(|n| (n, (pending, last)))
+                        }
+                    },
+                )))
+            }
+        }
+    }
 }
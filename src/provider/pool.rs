@@ -0,0 +1,425 @@
+//! Load balancing JSON-RPC client over several HTTP endpoints.
+
+use crate::config::EndpointConfig;
+use crate::metrics::METRICS;
+use async_trait::async_trait;
+use ethers::prelude::*;
+use ethers::providers::{
+    HttpClientError, HttpRateLimitRetryPolicy, JsonRpcError, RetryClientError, RetryPolicy,
+    RpcError,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Consecutive transport failures before an endpoint is taken out of rotation.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+/// Retries of rate limited batch calls, as `RetryClientBuilder` defaults to for single requests.
+const RATE_LIMIT_RETRIES: u32 = 10;
+/// Backoff before a rate limited batch call is resent, unless the node asks for longer.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(1000);
+
+#[derive(Debug, thiserror::Error)]
+pub enum PoolError {
+    #[error(transparent)]
+    Client(#[from] RetryClientError),
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error("malformed batch response: {0}")]
+    MalformedBatch(String),
+    #[error("no healthy endpoint available")]
+    NoHealthyEndpoint,
+    #[error("provider pool needs at least one endpoint")]
+    NoEndpoint,
+    #[error("invalid endpoint url {0}: {1}")]
+    InvalidUrl(String, url::ParseError),
+    #[error("endpoint {0} has a weight of 0, remove it to disable it")]
+    ZeroWeight(String),
+}
+
+impl RpcError for PoolError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            PoolError::Client(e) => e.as_error_response(),
+            PoolError::Http(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            PoolError::Client(e) => e.as_serde_error(),
+            PoolError::Http(e) => e.as_serde_error(),
+            PoolError::SerdeJson(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PoolError> for ProviderError {
+    fn from(src: PoolError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(src))
+    }
+}
+
+/// One response object of a JSON-RPC batch.
+#[derive(Deserialize)]
+struct BatchResponse {
+    id: Value,
+    #[serde(default)]
+    result: Value,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: Url,
+    client: RetryClient<Http>,
+    /// Raw HTTP client for batch requests, which `Http` does not support.
+    http: reqwest::Client,
+    weight: i64,
+    /// Minimum spacing between two requests, derived from the rate limit.
+    period: Option<Duration>,
+    next_slot: Mutex<Instant>,
+    healthy: AtomicBool,
+    failures: AtomicU32,
+}
+
+impl Endpoint {
+    fn new(config: &EndpointConfig) -> Result<Self, PoolError> {
+        let url = config
+            .url
+            .parse::<Url>()
+            .map_err(|e| PoolError::InvalidUrl(config.url.clone(), e))?;
+        if config.weight == 0 {
+            return Err(PoolError::ZeroWeight(config.url.clone()));
+        }
+        let client = RetryClientBuilder::default()
+            .build(Http::new(url.clone()), Box::new(HttpRateLimitRetryPolicy));
+        Ok(Self {
+            url,
+            client,
+            http: reqwest::Client::new(),
+            weight: config.weight as i64,
+            period: config
+                .rate_limit
+                .map(|rps| Duration::from_secs_f64(1.0 / rps.max(1) as f64)),
+            next_slot: Mutex::new(Instant::now()),
+            healthy: AtomicBool::new(true),
+            failures: AtomicU32::new(0),
+        })
+    }
+
+    /// Wait until the endpoint's rate limit allows `n` more requests.
+    async fn throttle(&self, n: u32) {
+        let Some(period) = self.period else {
+            return;
+        };
+        let wait = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + period * n;
+            slot - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Send a batch, resending the calls that are rate limited under the policy the
+    /// [`RetryClient`] of single requests uses, with the same backoff.
+    async fn send_batch(&self, body: &[Value]) -> Result<Vec<BatchResponse>, PoolError> {
+        let mut responses = Vec::with_capacity(body.len());
+        let mut pending = body.to_vec();
+        let mut retries = 0;
+        loop {
+            self.throttle(pending.len() as u32).await;
+            let (limited, backoff) = match self.post_batch(&pending).await {
+                Ok(batch) => {
+                    let mut limited = vec![];
+                    let mut backoff = None;
+                    for response in batch {
+                        let error = response.error.clone().map(HttpClientError::JsonRpcError);
+                        match error {
+                            Some(error)
+                                if retries < RATE_LIMIT_RETRIES
+                                    && HttpRateLimitRetryPolicy.should_retry(&error) =>
+                            {
+                                backoff =
+                                    backoff.max(HttpRateLimitRetryPolicy.backoff_hint(&error));
+                                limited.push(response.id);
+                            }
+                            _ => responses.push(response),
+                        }
+                    }
+                    (limited, backoff)
+                }
+                // the whole batch was rejected
+                Err(e)
+                    if retries < RATE_LIMIT_RETRIES
+                        && HttpRateLimitRetryPolicy.should_retry(&e) =>
+                {
+                    let ids = pending.iter().map(|call| call["id"].clone());
+                    (ids.collect(), HttpRateLimitRetryPolicy.backoff_hint(&e))
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if limited.is_empty() {
+                return Ok(responses);
+            }
+            retries += 1;
+            let backoff = backoff.unwrap_or(INITIAL_BACKOFF);
+            debug!(
+                "{} batch calls rate limited by {}, retrying in {:?}",
+                limited.len(),
+                self.url,
+                backoff
+            );
+            tokio::time::sleep(backoff).await;
+            pending.retain(|call| limited.contains(&call["id"]));
+        }
+    }
+
+    async fn post_batch(&self, body: &[Value]) -> Result<Vec<BatchResponse>, HttpClientError> {
+        let text = self
+            .http
+            .post(self.url.clone())
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        // a batch rejected as a whole gets a single error object, which the policy inspects
+        serde_json::from_str(&text).map_err(|err| HttpClientError::SerdeJson { err, text })
+    }
+
+    fn record_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        if !self.healthy.swap(true, Ordering::Relaxed) {
+            info!("endpoint {} is healthy again", self.url);
+        }
+    }
+
+    fn record_failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= MAX_CONSECUTIVE_FAILURES && self.healthy.swap(false, Ordering::Relaxed) {
+            warn!(
+                "endpoint {} marked unhealthy after {} failures",
+                self.url, failures
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    endpoints: Vec<Endpoint>,
+    /// Current weights of the smooth weighted round-robin.
+    current_weights: Mutex<Vec<i64>>,
+}
+
+/// A [`JsonRpcClient`] distributing requests over several endpoints.
+///
+/// Endpoints are picked by smooth weighted round-robin among the healthy ones. A request that
+/// fails on the transport level is retried on the next endpoint, JSON-RPC error responses are
+/// returned as is since another node would answer the same.
+#[derive(Clone, Debug)]
+pub struct PoolClient {
+    inner: Arc<Inner>,
+}
+
+impl PoolClient {
+    pub fn new(endpoints: &[EndpointConfig]) -> Result<Self, PoolError> {
+        if endpoints.is_empty() {
+            return Err(PoolError::NoEndpoint);
+        }
+        let endpoints = endpoints
+            .iter()
+            .map(Endpoint::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let current_weights = Mutex::new(vec![0; endpoints.len()]);
+        Ok(Self {
+            inner: Arc::new(Inner {
+                endpoints,
+                current_weights,
+            }),
+        })
+    }
+
+    /// Order in which endpoints are tried for the next request.
+    fn schedule(&self) -> Vec<usize> {
+        let endpoints = &self.inner.endpoints;
+        let healthy = endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.healthy.load(Ordering::Relaxed))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        // when every endpoint is down, keep trying all of them rather than failing outright
+        let candidates = if healthy.is_empty() {
+            (0..endpoints.len()).collect()
+        } else {
+            healthy
+        };
+
+        let mut current_weights = self.inner.current_weights.lock().unwrap();
+        let total = candidates.iter().map(|i| endpoints[*i].weight).sum::<i64>();
+        for i in candidates.iter() {
+            current_weights[*i] += endpoints[*i].weight;
+        }
+        let selected = *candidates
+            .iter()
+            .max_by_key(|i| current_weights[**i])
+            .unwrap();
+        current_weights[selected] -= total;
+
+        let mut order = vec![selected];
+        order.extend(candidates.into_iter().filter(|i| *i != selected));
+        order
+    }
+
+    /// Send the same method with each of `params` as one JSON-RPC batch.
+    ///
+    /// The batch as a whole fails over like a single request, errors of individual calls are
+    /// returned in place of their results once rate limited calls ran out of retries.
+    pub async fn batch_request<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<Result<R, JsonRpcError>>, PoolError> {
+        if params.is_empty() {
+            return Ok(vec![]);
+        }
+        let body = params
+            .into_iter()
+            .enumerate()
+            .map(|(id, params)| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let _timer = METRICS
+            .rpc_duration
+            .with_label_values(&[method])
+            .start_timer();
+        let mut last_error = PoolError::NoHealthyEndpoint;
+        for index in self.schedule() {
+            let endpoint = &self.inner.endpoints[index];
+            match endpoint.send_batch(&body).await {
+                Ok(responses) => {
+                    endpoint.record_success();
+                    return collect_batch(responses, &body);
+                }
+                Err(e) => {
+                    warn!("batch {} failed on {}: {}", method, endpoint.url, e);
+                    endpoint.record_failure();
+                    last_error = e;
+                }
+            }
+        }
+        METRICS.rpc_errors.with_label_values(&[method]).inc();
+        Err(last_error)
+    }
+
+    /// Periodically probe every endpoint with `eth_blockNumber`, so that failed endpoints
+    /// are brought back into rotation once they recover.
+    pub fn spawn_health_check(&self, interval: Duration) -> tokio::task::JoinHandle<()> {
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                for endpoint in inner.endpoints.iter() {
+                    let result: Result<U64, _> =
+                        JsonRpcClient::request(&endpoint.client, "eth_blockNumber", ()).await;
+                    match result {
+                        Ok(_) => endpoint.record_success(),
+                        Err(e) => {
+                            debug!("health check of {} failed: {}", endpoint.url, e);
+                            endpoint.record_failure();
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Put batch responses back into the order of the calls in `body`, they may arrive in any
+/// order.
+fn collect_batch<R: DeserializeOwned>(
+    responses: Vec<BatchResponse>,
+    body: &[Value],
+) -> Result<Vec<Result<R, JsonRpcError>>, PoolError> {
+    let mut ordered = body.iter().map(|_| None).collect::<Vec<_>>();
+    for response in responses {
+        let slot = body
+            .iter()
+            .position(|call| call["id"] == response.id)
+            .and_then(|i| ordered.get_mut(i))
+            .ok_or_else(|| PoolError::MalformedBatch(format!("unknown id {}", response.id)))?;
+        *slot = Some(match response.error {
+            Some(error) => Err(error),
+            None => Ok(serde_json::from_value(response.result)?),
+        });
+    }
+    ordered
+        .into_iter()
+        .zip(body)
+        .map(|(r, call)| {
+            r.ok_or_else(|| PoolError::MalformedBatch(format!("missing id {}", call["id"])))
+        })
+        .collect()
+}
+
+#[async_trait]
+impl JsonRpcClient for PoolClient {
+    type Error = PoolError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let params = serde_json::to_value(params)?;
+        let _timer = METRICS
+            .rpc_duration
+            .with_label_values(&[method])
+            .start_timer();
+        let mut last_error = PoolError::NoHealthyEndpoint;
+        for index in self.schedule() {
+            let endpoint = &self.inner.endpoints[index];
+            endpoint.throttle(1).await;
+            match JsonRpcClient::request(&endpoint.client, method, &params).await {
+                Ok(r) => {
+                    endpoint.record_success();
+                    return Ok(r);
+                }
+                Err(e) if e.is_error_response() || e.is_serde_error() => {
+                    endpoint.record_success();
+                    METRICS.rpc_errors.with_label_values(&[method]).inc();
+                    return Err(e.into());
+                }
+                Err(e) => {
+                    warn!("{} failed on {}: {}", method, endpoint.url, e);
+                    endpoint.record_failure();
+                    last_error = e.into();
+                }
+            }
+        }
+        METRICS.rpc_errors.with_label_values(&[method]).inc();
+        Err(last_error)
+    }
+}
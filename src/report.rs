@@ -0,0 +1,470 @@
+//! Aggregation of the recorded opcode statistics.
+
+use crate::evm::OpcodeId;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqlitePool};
+
+/// Which statistics to aggregate.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Statistics {
+    /// Opcodes in deployed contracts.
+    #[default]
+    Deployed,
+    /// Opcodes executed in traced blocks.
+    Executed,
+    /// Opcodes in called contracts, weighted by their number of calls.
+    Weighted,
+    /// Opcodes in deployed EOF contracts.
+    Eof,
+}
+
+impl Statistics {
+    /// Name of the table or view holding the statistics.
+    pub fn table(&self) -> &'static str {
+        match self {
+            Statistics::Deployed => "opcode_statistics",
+            Statistics::Executed => "executed_opcode_statistics",
+            Statistics::Weighted => "weighted_opcode_statistics",
+            Statistics::Eof => "eof_opcode_statistics",
+        }
+    }
+
+    /// Name of an opcode byte, the one recorded in the `opcode` table for legacy statistics.
+    fn name(&self, opcode: u8, recorded: Option<String>) -> String {
+        match self {
+            Statistics::Eof => OpcodeId::from_eof(opcode).to_string(),
+            _ => recorded.unwrap_or_else(|| OpcodeId::from(opcode).to_string()),
+        }
+    }
+}
+
+/// Time bucket of bucketed statistics, in UTC.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Hour,
+    Day,
+    /// Weeks starting on Monday.
+    Week,
+    Month,
+}
+
+impl Bucket {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+            Bucket::Week => "week",
+            Bucket::Month => "month",
+        }
+    }
+
+    /// SQLite expression of the bucket label of a `blocks.timestamp`.
+    fn sql(&self) -> &'static str {
+        match self {
+            Bucket::Hour => "strftime('%Y-%m-%dT%H:00', blocks.timestamp, 'unixepoch')",
+            Bucket::Day => "date(blocks.timestamp, 'unixepoch')",
+            Bucket::Week => "date(blocks.timestamp, 'unixepoch', '-6 days', 'weekday 1')",
+            Bucket::Month => "strftime('%Y-%m', blocks.timestamp, 'unixepoch')",
+        }
+    }
+}
+
+/// Half-open block range, unbounded if `end` is `None`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BlockRange {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl BlockRange {
+    fn bounds(&self) -> (i64, i64) {
+        (
+            self.start as i64,
+            self.end.map(|end| end as i64).unwrap_or(i64::MAX),
+        )
+    }
+}
+
+#[derive(FromRow)]
+struct OpcodeCount {
+    opcode: i64,
+    name: Option<String>,
+    count: i64,
+}
+
+#[derive(FromRow)]
+struct BucketCount {
+    bucket: String,
+    opcode: i64,
+    name: Option<String>,
+    count: i64,
+    /// Count of all opcodes in the bucket.
+    total: i64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BucketRow {
+    /// Start of the bucket, e.g. `2023-05-01` for the week starting on that Monday.
+    pub bucket: String,
+    pub opcode: u8,
+    pub name: String,
+    pub count: u64,
+    /// Share of all opcodes in the bucket, in percent.
+    pub percentage: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportRow {
+    pub opcode: u8,
+    pub name: String,
+    pub count: u64,
+    /// Share of all opcodes in the range, in percent.
+    pub percentage: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compare_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compare_percentage: Option<f64>,
+    /// `percentage - compare_percentage`, in percentage points.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub chain_id: u64,
+    pub statistics: Statistics,
+    pub range: BlockRange,
+    pub total: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compare_range: Option<BlockRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compare_total: Option<u64>,
+    /// Calls in `range` to contracts without an opcode histogram, which the weighted
+    /// statistics leave out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unweighted_calls: Option<u64>,
+    pub rows: Vec<ReportRow>,
+}
+
+/// Opcode counts of the chain within `range`, most frequent first.
+pub async fn opcode_counts(
+    pool: &SqlitePool,
+    chain_id: u64,
+    statistics: Statistics,
+    range: BlockRange,
+) -> Result<Vec<(u8, String, u64)>, sqlx::Error> {
+    let (start, end) = range.bounds();
+    // the table name comes from a fixed set, it is safe to format into the query
+    let sql = format!(
+        r#"SELECT statistics.opcode AS opcode, opcode.name AS name, SUM(statistics.count) AS count
+        FROM {} AS statistics
+        LEFT JOIN opcode ON opcode.value = statistics.opcode
+        WHERE statistics.chain_id = ? AND statistics.block_number >= ? AND statistics.block_number < ?
+        GROUP BY statistics.opcode
+        ORDER BY count DESC"#,
+        statistics.table()
+    );
+    Ok(sqlx::query_as::<_, OpcodeCount>(&sql)
+        .bind(chain_id as i64)
+        .bind(start)
+        .bind(end)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| {
+            let opcode = r.opcode as u8;
+            (opcode, statistics.name(opcode, r.name), r.count as u64)
+        })
+        .collect())
+}
+
+/// Calls of the chain within `range` to contracts without an opcode histogram.
+pub async fn unweighted_calls(
+    pool: &SqlitePool,
+    chain_id: u64,
+    range: BlockRange,
+) -> Result<u64, sqlx::Error> {
+    let (start, end) = range.bounds();
+    let (count,) = sqlx::query_as::<_, (i64,)>(
+        r#"SELECT COALESCE(SUM(count), 0)
+        FROM unweighted_contract_calls
+        WHERE chain_id = ? AND block_number >= ? AND block_number < ?"#,
+    )
+    .bind(chain_id as i64)
+    .bind(start)
+    .bind(end)
+    .fetch_one(pool)
+    .await?;
+    Ok(count as u64)
+}
+
+/// Build a report of the `top` most frequent opcodes in `range`, optionally compared with
+/// their frequency in `compare_range`.
+///
+/// Only `opcodes` are reported if not empty, their percentages remain shares of all opcodes.
+pub async fn build_report(
+    pool: &SqlitePool,
+    chain_id: u64,
+    statistics: Statistics,
+    range: BlockRange,
+    compare_range: Option<BlockRange>,
+    opcodes: &[u8],
+    top: Option<usize>,
+) -> Result<Report, sqlx::Error> {
+    let mut counts = opcode_counts(pool, chain_id, statistics, range).await?;
+    let total = counts.iter().map(|(_, _, count)| count).sum::<u64>();
+
+    let compare = match compare_range {
+        Some(compare_range) => {
+            let compare_counts = opcode_counts(pool, chain_id, statistics, compare_range).await?;
+            let total = compare_counts
+                .iter()
+                .map(|(_, _, count)| count)
+                .sum::<u64>();
+            let mut by_opcode = [0u64; 256];
+            for (opcode, name, count) in compare_counts {
+                // opcodes which only occur in the compare range come last
+                if !counts.iter().any(|(o, _, _)| *o == opcode) {
+                    counts.push((opcode, name, 0));
+                }
+                by_opcode[opcode as usize] = count;
+            }
+            Some((by_opcode, total))
+        }
+        None => None,
+    };
+    if !opcodes.is_empty() {
+        counts.retain(|(opcode, _, _)| opcodes.contains(opcode));
+    }
+
+    let rows = counts
+        .into_iter()
+        .take(top.unwrap_or(usize::MAX))
+        .map(|(opcode, name, count)| {
+            let percentage = percentage(count, total);
+            let (compare_count, compare_percentage) = match &compare {
+                Some((by_opcode, total)) => {
+                    let count = by_opcode[opcode as usize];
+                    (Some(count), Some(self::percentage(count, *total)))
+                }
+                None => (None, None),
+            };
+            ReportRow {
+                opcode,
+                name,
+                count,
+                percentage,
+                compare_count,
+                compare_percentage,
+                delta: compare_percentage.map(|p| percentage - p),
+            }
+        })
+        .collect();
+
+    let unweighted_calls = match statistics {
+        Statistics::Weighted => Some(unweighted_calls(pool, chain_id, range).await?),
+        _ => None,
+    };
+
+    Ok(Report {
+        chain_id,
+        statistics,
+        range,
+        total,
+        compare_range,
+        compare_total: compare.map(|(_, total)| total),
+        unweighted_calls,
+        rows,
+    })
+}
+
+/// Opcode counts within `range` per time bucket, ordered by bucket and most frequent first.
+///
+/// Only blocks with a recorded timestamp are included, and only `opcodes` if not empty, whose
+/// percentages remain shares of all opcodes in the bucket.
+pub async fn bucketed_opcode_counts(
+    pool: &SqlitePool,
+    chain_id: u64,
+    statistics: Statistics,
+    range: BlockRange,
+    bucket: Bucket,
+    opcodes: &[u8],
+) -> Result<Vec<BucketRow>, sqlx::Error> {
+    let (start, end) = range.bounds();
+    let filter = match opcodes.len() {
+        0 => String::new(),
+        n => format!("WHERE opcode IN ({})", vec!["?"; n].join(", ")),
+    };
+    // the table name and bucket expression come from fixed sets, the bucket totals are taken
+    // before the opcode filter
+    let sql = format!(
+        r#"SELECT * FROM (
+            SELECT {} AS bucket, statistics.opcode AS opcode, opcode.name AS name,
+                SUM(statistics.count) AS count,
+                SUM(SUM(statistics.count)) OVER (PARTITION BY {0}) AS total
+            FROM {} AS statistics
+            JOIN blocks ON blocks.chain_id = statistics.chain_id AND blocks.block_number = statistics.block_number
+            LEFT JOIN opcode ON opcode.value = statistics.opcode
+            WHERE statistics.chain_id = ? AND statistics.block_number >= ? AND statistics.block_number < ?
+            GROUP BY bucket, statistics.opcode
+        )
+        {}
+        ORDER BY bucket, count DESC"#,
+        bucket.sql(),
+        statistics.table(),
+        filter
+    );
+    let mut query = sqlx::query_as::<_, BucketCount>(&sql)
+        .bind(chain_id as i64)
+        .bind(start)
+        .bind(end);
+    for opcode in opcodes {
+        query = query.bind(*opcode as i64);
+    }
+    let rows = query
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| {
+            let opcode = r.opcode as u8;
+            BucketRow {
+                bucket: r.bucket,
+                opcode,
+                name: statistics.name(opcode, r.name),
+                count: r.count as u64,
+                percentage: percentage(r.count as u64, r.total as u64),
+            }
+        })
+        .collect();
+    Ok(rows)
+}
+
+fn percentage(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        append_contract_calls, append_eof_opcode_statistics, append_opcode_statistics,
+        memory_sqlite, set_contract_opcode_statistics,
+    };
+    use ethers::types::Address;
+
+    /// PUSH0 3 and 1 times and ADD 1 and 5 times in blocks 1 and 2.
+    async fn pool_with_statistics() -> SqlitePool {
+        let pool = memory_sqlite().await;
+        for (block_number, push0, add) in [(1, 3, 1), (2, 1, 5)] {
+            append_opcode_statistics(&pool, 1, block_number, OpcodeId::PUSH0.as_u8(), push0)
+                .await
+                .unwrap();
+            append_opcode_statistics(&pool, 1, block_number, OpcodeId::ADD.as_u8(), add)
+                .await
+                .unwrap();
+        }
+        pool
+    }
+
+    fn range(start: u64, end: u64) -> BlockRange {
+        BlockRange {
+            start,
+            end: Some(end),
+        }
+    }
+
+    #[tokio::test]
+    async fn compare_ranges() {
+        let pool = pool_with_statistics().await;
+        let report = build_report(
+            &pool,
+            1,
+            Statistics::Deployed,
+            range(2, 3),
+            Some(range(1, 2)),
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!((report.total, report.compare_total), (6, Some(4)));
+        let rows = report
+            .rows
+            .iter()
+            .map(|r| (r.name.as_str(), r.count, r.compare_count, r.delta))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                ("ADD", 5, Some(1), Some(500.0 / 6.0 - 25.0)),
+                ("PUSH0", 1, Some(3), Some(100.0 / 6.0 - 75.0)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn opcode_filter_before_top() {
+        let pool = pool_with_statistics().await;
+        let report = build_report(
+            &pool,
+            1,
+            Statistics::Deployed,
+            BlockRange::default(),
+            None,
+            &[OpcodeId::PUSH0.as_u8()],
+            Some(1),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.total, 10);
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].opcode, OpcodeId::PUSH0.as_u8());
+        assert_eq!(report.rows[0].percentage, 40.0);
+        assert_eq!(report.unweighted_calls, None);
+    }
+
+    #[tokio::test]
+    async fn weighted_by_calls() {
+        let pool = memory_sqlite().await;
+        let (known, unknown) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        set_contract_opcode_statistics(&pool, 1, known, &[(OpcodeId::ADD.as_u8(), 2)], false)
+            .await
+            .unwrap();
+        append_contract_calls(&pool, 1, 1, known, 3).await.unwrap();
+        append_contract_calls(&pool, 1, 1, unknown, 4)
+            .await
+            .unwrap();
+        let report = build_report(
+            &pool,
+            1,
+            Statistics::Weighted,
+            BlockRange::default(),
+            None,
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.total, 6);
+        assert_eq!(report.unweighted_calls, Some(4));
+    }
+
+    #[tokio::test]
+    async fn eof_names() {
+        let pool = memory_sqlite().await;
+        // 0xe0 is RJUMP in EOF code, unassigned in legacy code
+        append_eof_opcode_statistics(&pool, 1, 1, 0xe0, 1)
+            .await
+            .unwrap();
+        let counts = opcode_counts(&pool, 1, Statistics::Eof, BlockRange::default())
+            .await
+            .unwrap();
+        assert_eq!(counts, [(0xe0, "RJUMP".to_string(), 1)]);
+    }
+}
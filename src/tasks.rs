@@ -1,161 +1,446 @@
-use crate::consts::{CONTRACT_TREE, INIT_CODE_TREE, TX_CONTRACT_ADDRESS_TREE};
+use crate::analysis::{ContractContext, Registry};
+use crate::chain::Chain;
+use crate::config::{CodeSource, OpcodeTracer};
+use crate::consts::{
+    CONTRACT_TREE, INIT_CODE_TREE, TX_BLOCK_NUMBER_TREE, TX_CONTRACT_ADDRESS_TREE,
+};
 use crate::db::*;
-use crate::evm::{Bytecode, OpcodeId};
+use crate::evm::{BytecodeView, OpcodeId};
+use crate::executor::{execute_init_code, spec_id};
+use crate::metrics::METRICS;
+use crate::provider::{HeadFollower, PoolClient, PoolProvider};
 use ethers::prelude::*;
+use ethers::providers::JsonRpcError;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use sqlx::SqlitePool;
+use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 #[instrument(skip_all)]
 pub async fn listen_blocks(
     pool: SqlitePool,
+    chain_id: u64,
     metadata: sled::Tree,
-    provider: Provider<Ws>,
+    follower: HeadFollower,
+    start_block: u64,
     running: Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
+    let mut latest_recorded_block = get_latest_recorded_block(&metadata, start_block)?;
     while running.load(std::sync::atomic::Ordering::SeqCst) {
-        let latest_recorded_block = get_latest_recorded_block(&metadata)?;
-        let latest_block = provider.get_block_number().await?.as_u64();
+        latest_recorded_block = get_latest_recorded_block(&metadata, start_block)?;
+        let latest_block = follower.get_block_number().await?;
+        METRICS.head_block.set(latest_block as i64);
         info!("Latest recorded block is #{}", latest_recorded_block);
         info!("Latest block is #{}", latest_block);
         if latest_recorded_block >= latest_block {
             break;
         }
         for block_number in (latest_recorded_block + 1)..=latest_block {
-            submit_block_task(&pool, block_number).await?;
+            submit_block_task(&pool, chain_id, block_number).await?;
             set_latest_recorded_block(&metadata, block_number)?;
         }
     }
 
     info!("catch up done, listening for new blocks");
-    let mut block_stream = provider.subscribe_blocks().await?;
+    let mut block_stream = follower
+        .new_blocks(latest_recorded_block, running.clone())
+        .await?;
 
-    while let Some(block) = block_stream.next().await {
+    while let Some(block_number) = block_stream.next().await {
         if !running.load(std::sync::atomic::Ordering::SeqCst) {
             break;
         }
-        let block_number = block.number.unwrap().as_u64();
-        info!("new block #{} {}", block_number, block.hash.unwrap());
-        submit_block_task(&pool, block_number).await?;
+        info!("new block #{}", block_number);
+        METRICS.head_block.set(block_number as i64);
+        submit_block_task(&pool, chain_id, block_number).await?;
         set_latest_recorded_block(&metadata, block_number)?;
     }
 
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(skip_all, fields(worker_id = %worker_id))]
 pub async fn handle_block(
     worker_id: usize,
     pool: SqlitePool,
+    chain_id: u64,
     sled_db: sled::Db,
-    provider: Provider<impl JsonRpcClient>,
+    provider: PoolProvider,
+    batch_size: u32,
+    trace_blocks: bool,
     running: Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
     let init_code_db = sled_db.open_tree(INIT_CODE_TREE)?;
+    let tx_block_db = sled_db.open_tree(TX_BLOCK_NUMBER_TREE)?;
+    let client = provider.as_ref();
     while running.load(std::sync::atomic::Ordering::SeqCst) {
-        let guard = BlockTaskGuard::new(&pool).await?;
-        if guard.is_none() {
+        let guards = BlockTaskGuard::new_batch(&pool, chain_id, batch_size).await?;
+        if guards.is_empty() {
             // sleep
             info!("no block task, sleep");
             tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
             continue;
         }
-        let guard = guard.unwrap();
-        let block = provider
-            .get_block_with_txs(guard.block_number())
-            .await?
-            .unwrap();
-        assert_eq!(block.number.unwrap().as_u64(), guard.block_number());
-        trace!(
-            worker_id,
-            "fetching block #{} {}",
-            block.number.unwrap().as_u64(),
-            block.hash.unwrap()
-        );
-        let mut counter = 0;
-        for tx in block.transactions.iter() {
-            if tx.to.is_some() {
+        let blocks = client
+            .batch_request::<Option<Block<Transaction>>>(
+                "eth_getBlockByNumber",
+                guards
+                    .iter()
+                    .map(|g| json!([U64::from(g.block_number()), true]))
+                    .collect(),
+            )
+            .await?;
+
+        let mut processed = 0;
+        for (guard, block) in guards.into_iter().zip(blocks) {
+            // dropping the guard leaves the task pending for the next round
+            let block = match block {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    warn!("block #{} not found, retry later", guard.block_number());
+                    continue;
+                }
+                Err(e) => {
+                    warn!("failed to fetch block #{}: {}", guard.block_number(), e);
+                    continue;
+                }
+            };
+            if block.number.map(|n| n.as_u64()) != Some(guard.block_number()) {
+                warn!(
+                    "node returned block {:?} for #{}, retry later",
+                    block.number,
+                    guard.block_number()
+                );
                 continue;
             }
-            init_code_db.insert(tx.hash().as_bytes(), tx.input.as_ref())?;
-            submit_tx_task(&pool, tx.hash()).await?;
-            counter += 1;
+            trace!(
+                worker_id,
+                "fetching block #{} {:?}",
+                guard.block_number(),
+                block.hash
+            );
+            set_block_timestamp(
+                &pool,
+                chain_id,
+                guard.block_number(),
+                block.timestamp.as_u64(),
+            )
+            .await?;
+            let mut counter = 0;
+            // top-level calls only, txs without input are plain transfers
+            let mut calls = HashMap::<Address, u64>::new();
+            for tx in block.transactions.iter() {
+                if let Some(to) = tx.to {
+                    if !tx.input.is_empty() {
+                        *calls.entry(to).or_default() += 1;
+                    }
+                    continue;
+                }
+                init_code_db.insert(tx.hash().as_bytes(), tx.input.as_ref())?;
+                tx_block_db.insert(tx.hash().as_bytes(), &guard.block_number().to_be_bytes())?;
+                submit_tx_task(&pool, chain_id, tx.hash()).await?;
+                counter += 1;
+            }
+            if counter != 0 {
+                trace!("fetched {} create txs", counter);
+            }
+            for (address, count) in calls {
+                append_contract_calls(&pool, chain_id, guard.block_number(), address, count)
+                    .await?;
+            }
+            if trace_blocks {
+                submit_trace_task(&pool, chain_id, guard.block_number()).await?;
+            }
+            guard.complete();
+            processed += 1;
+            METRICS.blocks_processed.inc();
         }
-        if counter != 0 {
-            trace!("fetched {} create txs", counter);
+        if processed == 0 {
+            // every block of the batch failed, don't hammer the node with the same batch
+            tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
         }
-        guard.complete();
     }
     info!("gracefully shutdown");
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 #[instrument(skip_all, fields(worker_id = %worker_id))]
 pub async fn handle_tx(
     worker_id: usize,
     pool: SqlitePool,
+    chain: Chain,
     sled_db: sled::Db,
-    provider: Provider<impl JsonRpcClient>,
+    provider: PoolProvider,
+    batch_size: u32,
+    code_sources: Vec<CodeSource>,
+    analyzers: Arc<Registry>,
     running: Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
     let tx_contract_db = sled_db.open_tree(TX_CONTRACT_ADDRESS_TREE)?;
     let contract_db = sled_db.open_tree(CONTRACT_TREE)?;
+    let init_code_db = sled_db.open_tree(INIT_CODE_TREE)?;
+    let client = provider.as_ref();
     while running.load(std::sync::atomic::Ordering::SeqCst) {
-        let guard = TxTaskGuard::new(&pool).await?;
-        if guard.is_none() {
+        let guards = TxTaskGuard::new_batch(&pool, chain.id(), batch_size).await?;
+        if guards.is_empty() {
             // sleep
             info!("no tx task, sleep");
             tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
             continue;
         }
-        let guard = guard.unwrap();
-        let tx_hash = guard.tx_hash();
-        let tx = provider.get_transaction_receipt(tx_hash).await?.unwrap();
-        if tx.status.unwrap().as_u64() == 0 {
-            trace!("skip failed tx {}", tx_hash);
-            guard.complete();
-            continue;
+        // receipts are fetched per tx rather than with `eth_getBlockReceipts`: a batch holds
+        // creations of many blocks, which are few among a block's txs, and not every node
+        // serves the block method
+        let receipts = client
+            .batch_request::<Option<TransactionReceipt>>(
+                "eth_getTransactionReceipt",
+                guards.iter().map(|g| json!([g.tx_hash()])).collect(),
+            )
+            .await?;
+
+        let mut processed = 0;
+        let mut deployments = vec![];
+        for (guard, receipt) in guards.into_iter().zip(receipts) {
+            let tx_hash = guard.tx_hash();
+            // dropping the guard leaves the task pending for the next round
+            let tx = match receipt {
+                Ok(Some(tx)) => tx,
+                Ok(None) => {
+                    warn!("receipt of tx {:?} not found, retry later", tx_hash);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("failed to fetch receipt of tx {:?}: {}", tx_hash, e);
+                    continue;
+                }
+            };
+            if tx.status.is_some_and(|status| status.is_zero()) {
+                trace!("skip failed tx {}", tx_hash);
+                guard.complete();
+                processed += 1;
+                METRICS.tx_tasks_processed.inc();
+                continue;
+            }
+            let (Some(contract_address), Some(block_number)) =
+                (tx.contract_address, tx.block_number)
+            else {
+                warn!(
+                    "receipt of tx {:?} lacks the contract address or block, retry later",
+                    tx_hash
+                );
+                continue;
+            };
+            trace!(
+                "analyze tx {} deployed to contract {}",
+                tx_hash,
+                contract_address
+            );
+            let timestamp = get_block_timestamp(&pool, chain.id(), block_number.as_u64()).await?;
+            let deployment = Deployment {
+                tx_hash,
+                contract_address,
+                block_number,
+                timestamp,
+            };
+            deployments.push((guard, deployment));
         }
-        let contract_address = tx.contract_address.unwrap();
-        trace!(
-            "analyze tx {} deployed to contract {}",
-            tx_hash,
-            contract_address
-        );
-        let code = provider.get_code(contract_address, None).await?;
-        if code.is_empty() {
-            trace!("skip empty contract {}", contract_address);
+
+        let codes = fetch_deployed_codes(
+            client,
+            chain,
+            &init_code_db,
+            &deployments.iter().map(|(_, d)| *d).collect::<Vec<_>>(),
+            &code_sources,
+        )
+        .await?;
+
+        for ((guard, deployment), code) in deployments.into_iter().zip(codes) {
+            let Deployment {
+                tx_hash,
+                contract_address,
+                block_number,
+                timestamp,
+            } = deployment;
+            let Some(code) = code else {
+                // dropping the guard leaves the task pending until a source serves it
+                warn!(
+                    "no code source could serve contract {:?}, retry later",
+                    contract_address
+                );
+                continue;
+            };
+            if code.is_empty() {
+                trace!("skip empty contract {}", contract_address);
+                guard.complete();
+                processed += 1;
+                METRICS.tx_tasks_processed.inc();
+                continue;
+            }
+            tx_contract_db.insert(tx_hash.as_bytes(), contract_address.as_bytes())?;
+            contract_db.insert(contract_address.as_bytes(), code.as_ref())?;
+            let ctx = ContractContext {
+                chain,
+                address: contract_address,
+                block_number: Some(block_number.as_u64()),
+                timestamp,
+            };
+            let outputs = analyzers.analyze(&ctx, &BytecodeView::new(&code));
+            analyzers.persist(&pool, &ctx, outputs).await?;
             guard.complete();
+            processed += 1;
+            METRICS.tx_tasks_processed.inc();
+        }
+        if processed == 0 {
+            // every tx of the batch failed, don't hammer the node with the same batch
+            tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
+        }
+    }
+    info!("gracefully shutdown");
+    Ok(())
+}
+
+/// Tracer aggregating executed opcodes on the node, so only the totals are transferred.
+const OPCODE_JS_TRACER: &str = r#"{
+    counts: {},
+    gas: {},
+    step: function(log) {
+        var op = log.op.toNumber();
+        this.counts[op] = (this.counts[op] || 0) + 1;
+        this.gas[op] = (this.gas[op] || 0) + log.getCost();
+    },
+    fault: function() {},
+    result: function() {
+        return { counts: this.counts, gas: this.gas };
+    }
+}"#;
+
+/// Per tx entry of `debug_traceBlockByNumber`.
+///
+/// Serializable only because `Middleware::request` requires it.
+#[derive(Debug, Serialize, Deserialize)]
+struct TxTrace<T> {
+    result: Option<T>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsOpcodeTrace {
+    counts: HashMap<u8, u64>,
+    gas: HashMap<u8, u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StructLogTrace {
+    struct_logs: Vec<StructLogEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StructLogEntry {
+    op: String,
+    gas_cost: u64,
+}
+
+/// Record executed opcode counts and gas of traced blocks.
+///
+/// The gas of call and create opcodes is the cost reported by the node, which for struct logs
+/// includes the gas forwarded to the callee.
+#[instrument(skip_all, fields(worker_id = %worker_id))]
+pub async fn handle_trace(
+    worker_id: usize,
+    pool: SqlitePool,
+    chain_id: u64,
+    provider: PoolProvider,
+    tracer: OpcodeTracer,
+    running: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    while running.load(std::sync::atomic::Ordering::SeqCst) {
+        let guard = TraceTaskGuard::new(&pool, chain_id).await?;
+        if guard.is_none() {
+            // sleep
+            info!("no trace task, sleep");
+            tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
             continue;
         }
-        tx_contract_db.insert(tx_hash.as_bytes(), contract_address.as_bytes())?;
-        contract_db.insert(contract_address.as_bytes(), code.as_ref())?;
-        let ops = Bytecode::from(code.to_vec());
-        let opcodes = ops
-            .code
-            .into_iter()
-            .filter(|op| op.is_code)
-            .map(|op| OpcodeId::from(op.value))
-            .collect::<Vec<_>>();
-        if opcodes.iter().any(|opcode| opcode.is_other_invalid()) {
-            warn!("contract {:?} contains invalid opcodes", contract_address,);
-        }
-
-        let count = opcodes
-            .iter()
-            .fold([0usize; 256], |mut acc, x| {
-                acc[x.as_u8() as usize] += 1;
-                acc
-            })
+        let guard = guard.unwrap();
+        let block_number = U64::from(guard.block_number());
+        trace!("tracing block #{}", block_number);
+
+        // (count, gas) per opcode
+        let mut statistics = [(0u64, 0u64); 256];
+        match tracer {
+            OpcodeTracer::Js => {
+                let traces: Vec<TxTrace<JsOpcodeTrace>> = provider
+                    .request(
+                        "debug_traceBlockByNumber",
+                        (block_number, json!({ "tracer": OPCODE_JS_TRACER })),
+                    )
+                    .await?;
+                for trace in traces
+                    .into_iter()
+                    .filter_map(|t| t.into_result(block_number))
+                {
+                    for (opcode, count) in trace.counts {
+                        statistics[opcode as usize].0 += count;
+                    }
+                    for (opcode, gas) in trace.gas {
+                        statistics[opcode as usize].1 += gas;
+                    }
+                }
+            }
+            OpcodeTracer::StructLogs => {
+                let options = json!({
+                    "disableStack": true,
+                    "disableStorage": true,
+                    "enableMemory": false,
+                    "enableReturnData": false,
+                });
+                let traces: Vec<TxTrace<StructLogTrace>> = provider
+                    .request("debug_traceBlockByNumber", (block_number, options))
+                    .await?;
+                // steps per opcode name that does not parse, reported once per block
+                let mut unknown = HashMap::<String, u64>::new();
+                for trace in traces
+                    .into_iter()
+                    .filter_map(|t| t.into_result(block_number))
+                {
+                    for log in trace.struct_logs {
+                        match log.op.parse::<OpcodeId>() {
+                            Ok(opcode) => {
+                                let entry = &mut statistics[opcode.as_u8() as usize];
+                                entry.0 += 1;
+                                entry.1 += log.gas_cost;
+                            }
+                            Err(_) => *unknown.entry(log.op).or_default() += 1,
+                        }
+                    }
+                }
+                if !unknown.is_empty() {
+                    warn!(
+                        "block #{}: steps of unknown opcodes not counted: {:?}",
+                        block_number, unknown
+                    );
+                }
+            }
+        }
+
+        let statistics = statistics
             .into_iter()
             .enumerate()
-            .filter(|(_, count)| *count > 0);
-        for (opcode, count) in count {
-            append_opcode_statistics(
+            .filter(|(_, (count, _))| *count > 0);
+        for (opcode, (count, gas)) in statistics {
+            append_executed_opcode_statistics(
                 &pool,
-                tx.block_number.unwrap().as_u64(),
+                chain_id,
+                guard.block_number(),
                 opcode as u8,
-                count as u64,
+                count,
+                gas,
             )
             .await?;
         }
@@ -164,3 +449,182 @@ pub async fn handle_tx(
     info!("gracefully shutdown");
     Ok(())
 }
+
+impl<T> TxTrace<T> {
+    fn into_result(self, block_number: U64) -> Option<T> {
+        if let Some(error) = self.error {
+            warn!("failed to trace a tx of block #{}: {}", block_number, error);
+        }
+        self.result
+    }
+}
+
+/// A contract deployment whose runtime code is to be fetched.
+#[derive(Copy, Clone)]
+struct Deployment {
+    tx_hash: H256,
+    contract_address: Address,
+    block_number: U64,
+    timestamp: Option<u64>,
+}
+
+/// Fetch the runtime code of each deployment, `None` for those no source could serve.
+///
+/// The sources are tried in order, a deployment falls through to the next source when it
+/// cannot be served by the current one, e.g. because the node's state is pruned, the debug
+/// namespace is disabled or the constructor fails against an empty local state.
+async fn fetch_deployed_codes(
+    client: &PoolClient,
+    chain: Chain,
+    init_code_db: &sled::Tree,
+    deployments: &[Deployment],
+    sources: &[CodeSource],
+) -> anyhow::Result<Vec<Option<Bytes>>> {
+    let mut result = vec![None; deployments.len()];
+    let mut pending = deployments.iter().enumerate().collect::<Vec<_>>();
+    for source in sources.iter() {
+        if pending.is_empty() {
+            break;
+        }
+        let batch = pending.iter().map(|(_, d)| *d).collect::<Vec<_>>();
+        let codes = fetch_codes(client, chain, init_code_db, *source, &batch).await?;
+        let mut unavailable = vec![];
+        for ((i, deployment), code) in pending.into_iter().zip(codes) {
+            match code {
+                Some(code) => result[i] = Some(code),
+                None => unavailable.push((i, deployment)),
+            }
+        }
+        if !unavailable.is_empty() {
+            warn!(
+                "{} contracts unavailable from {:?} code source",
+                unavailable.len(),
+                source
+            );
+        }
+        pending = unavailable;
+    }
+    Ok(result)
+}
+
+/// Fetch code of `deployments` from one source, `None` for those the source cannot serve.
+async fn fetch_codes(
+    client: &PoolClient,
+    chain: Chain,
+    init_code_db: &sled::Tree,
+    source: CodeSource,
+    deployments: &[&Deployment],
+) -> anyhow::Result<Vec<Option<Bytes>>> {
+    let codes = match source {
+        CodeSource::State => {
+            client
+                .batch_request(
+                    "eth_getCode",
+                    deployments
+                        .iter()
+                        .map(|d| json!([d.contract_address, d.block_number]))
+                        .collect(),
+                )
+                .await?
+        }
+        CodeSource::Latest => {
+            client
+                .batch_request(
+                    "eth_getCode",
+                    deployments
+                        .iter()
+                        .map(|d| json!([d.contract_address, "latest"]))
+                        .collect(),
+                )
+                .await?
+        }
+        CodeSource::Trace => {
+            // the top call frame of a contract creation returns the runtime code
+            let tracer = json!({ "tracer": "callTracer", "tracerConfig": { "onlyTopCall": true } });
+            client
+                .batch_request::<CallFrame>(
+                    "debug_traceTransaction",
+                    deployments
+                        .iter()
+                        .map(|d| json!([d.tx_hash, tracer]))
+                        .collect(),
+                )
+                .await?
+                .into_iter()
+                .map(|frame| frame.map(|f| f.output.unwrap_or_default()))
+                .collect()
+        }
+        CodeSource::Execute => {
+            // revm runs synchronously, keep it off the async workers
+            let init_code_db = init_code_db.clone();
+            let deployments = deployments.iter().map(|d| **d).collect::<Vec<_>>();
+            return tokio::task::spawn_blocking(move || {
+                deployments
+                    .iter()
+                    .map(|d| execute_deployment(&init_code_db, chain, d))
+                    .collect()
+            })
+            .await?;
+        }
+    };
+
+    codes
+        .into_iter()
+        .zip(deployments)
+        .map(|(code, d)| match code {
+            Ok(code) => Ok(Some(code)),
+            Err(e) if is_unavailable(&e) => {
+                trace!(
+                    "{:?} code of contract {} unavailable: {}",
+                    source,
+                    d.contract_address,
+                    e.message
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        })
+        .collect()
+}
+
+/// Re-run the deployment's init code locally.
+fn execute_deployment(
+    init_code_db: &sled::Tree,
+    chain: Chain,
+    deployment: &Deployment,
+) -> anyhow::Result<Option<Bytes>> {
+    let Some(init_code) = init_code_db.get(deployment.tx_hash.as_bytes())? else {
+        trace!("no init code of tx {}", deployment.tx_hash);
+        return Ok(None);
+    };
+    let spec = spec_id(
+        chain.profile(),
+        deployment.block_number.as_u64(),
+        deployment.timestamp,
+    );
+    let execution = execute_init_code(&init_code, spec)?;
+    if !execution.success {
+        trace!(
+            "constructor of contract {} failed locally",
+            deployment.contract_address
+        );
+        return Ok(None);
+    }
+    Ok(Some(execution.runtime_code.into()))
+}
+
+/// Whether a JSON-RPC error means the node cannot serve the request at all, rather than the
+/// request being wrong.
+fn is_unavailable(error: &JsonRpcError) -> bool {
+    const METHOD_NOT_FOUND: i64 = -32601;
+    const PATTERNS: [&str; 6] = [
+        "missing trie node",
+        "header not found",
+        "historical state",
+        "pruned",
+        "not available",
+        "does not exist",
+    ];
+    let message = error.message.to_lowercase();
+    error.code == METHOD_NOT_FOUND || PATTERNS.iter().any(|p| message.contains(p))
+}